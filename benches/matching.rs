@@ -0,0 +1,123 @@
+//! Benchmarks for finding files and working out their new names, run against a generated tree of 10,000 files.
+
+use std::fs::File;
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use regex::Regex;
+use renamer::{get_change_pairs, get_matched_paths, Config};
+use tempfile::TempDir;
+
+const DIRS: usize = 100;
+const FILES_PER_DIR: usize = 100;
+const NAMES: [&str; 5] = ["photo", "IMG_", "Report Final", "track-", "séance"];
+const EXTENSIONS: [&str; 5] = ["jpeg", "JPG", "txt", "mp3", "tar.gz"];
+
+/// 100 directories of 100 files, with a mix of names and extensions
+fn build_tree() -> TempDir {
+    let tree = TempDir::new().expect("Failed to create temporary directory");
+    for dir in 0..DIRS {
+        let dir_path = tree.path().join(format!("dir{dir:03}"));
+        std::fs::create_dir(&dir_path).expect("Failed to create directory");
+        for file in 0..FILES_PER_DIR {
+            let name = NAMES[file % NAMES.len()];
+            let extension = EXTENSIONS[(file / NAMES.len()) % EXTENSIONS.len()];
+            File::create(dir_path.join(format!("{name}{file:04}.{extension}")))
+                .expect("Failed to create file");
+        }
+    }
+    tree
+}
+
+/// the canonical path of the tree, which is what the paths are found under
+fn base_path(tree: &TempDir) -> String {
+    tree.path()
+        .canonicalize()
+        .expect("Failed to find the tree")
+        .to_string_lossy()
+        .to_string()
+}
+
+fn bench_get_matched_paths(c: &mut Criterion) {
+    let tree = build_tree();
+    let base_path = base_path(&tree);
+    let config = Config::builder()
+        .matcher(r".*\.jpeg")
+        .build()
+        .expect("Failed to build config");
+    let matcher_regex = Regex::new(r"^.*\.jpeg$").expect("Failed to build matcher");
+
+    c.bench_function("get_matched_paths 10,000 paths", |b| {
+        b.iter(|| {
+            get_matched_paths(&base_path, &config, matcher_regex.clone()).collect::<Vec<PathBuf>>()
+        })
+    });
+}
+
+fn bench_get_change_pairs(c: &mut Criterion) {
+    let tree = build_tree();
+    let base_path = base_path(&tree);
+    let config = Config::builder()
+        .matcher(".*")
+        .renamer(r"\.(txt)$")
+        .replacement("md")
+        .build()
+        .expect("Failed to build config");
+    let paths: Vec<PathBuf> = get_matched_paths(
+        &base_path,
+        &config,
+        Regex::new("^.*$").expect("Failed to build matcher"),
+    )
+    .collect();
+    let renamer_regex = Regex::new(r"\.(txt)$").expect("Failed to build renamer");
+
+    c.bench_function("get_change_pairs 10,000 paths", |b| {
+        b.iter_batched(
+            || paths.clone(),
+            |paths| {
+                get_change_pairs(
+                    paths,
+                    base_path.clone(),
+                    Some(renamer_regex.clone()),
+                    &config,
+                )
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_streamed(c: &mut Criterion) {
+    let tree = build_tree();
+    let base_path = base_path(&tree);
+    let config = Config::builder()
+        .matcher(".*")
+        .renamer(r"\.(txt)$")
+        .replacement("md")
+        .build()
+        .expect("Failed to build config");
+    let matcher_regex = Regex::new("^.*$").expect("Failed to build matcher");
+    let renamer_regex = Regex::new(r"\.(txt)$").expect("Failed to build renamer");
+
+    c.bench_function(
+        "get_matched_paths into get_change_pairs 10,000 paths",
+        |b| {
+            b.iter(|| {
+                get_change_pairs(
+                    get_matched_paths(&base_path, &config, matcher_regex.clone()),
+                    base_path.clone(),
+                    Some(renamer_regex.clone()),
+                    &config,
+                )
+            })
+        },
+    );
+}
+
+criterion_group!(
+    benches,
+    bench_get_matched_paths,
+    bench_get_change_pairs,
+    bench_streamed
+);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+//! `--rename-by-audio-tags`, which makes the tags of MP3 (ID3), Ogg and FLAC (Vorbis comment) files
+//! available to `--rename-template`, eg `{{artist}} - {{title}}.{{ext}}`
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use lofty::prelude::*;
+
+/// the template variables which come from the tags
+pub const AUDIO_TAGS: [&str; 5] = ["title", "artist", "album", "track_number", "year"];
+
+/// reads the tags the file has, empty ones are left out and they're made safe to use in a filename
+pub fn read_audio_tags(path: &Path) -> Result<HashMap<String, String>, String> {
+    let tagged_file = lofty::read_from_path(path)
+        .map_err(|err| format!("Couldn't read the tags of {path:?}: {err}"))?;
+    let mut tags = HashMap::new();
+    let Some(tag) = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+    else {
+        return Ok(tags);
+    };
+
+    let values = [
+        ("title", tag.title().map(|title| title.to_string())),
+        ("artist", tag.artist().map(|artist| artist.to_string())),
+        ("album", tag.album().map(|album| album.to_string())),
+        ("track_number", tag.track().map(|track| track.to_string())),
+        ("year", tag.date().map(|date| date.year.to_string())),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value.filter(|value| !value.trim().is_empty()) {
+            tags.insert(name.to_string(), crate::change_pairs::windows_safe(&value));
+        }
+    }
+    Ok(tags)
+}
@@ -0,0 +1,899 @@
+//! Working out where each matched path should be renamed to, see [get_change_pairs].
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::Datelike;
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::{Config, DateSource, NormForm, RenamePart, SpaceMode};
+#[cfg(feature = "lua")]
+use crate::lua;
+use crate::{audio_tags, ext_map, pdf_metadata, template};
+
+/// common extensions and what they're usually shortened to
+pub const EXTENSION_SUGGESTIONS: [(&str, &str); 6] = [
+    ("jpeg", "jpg"),
+    ("tiff", "tif"),
+    ("mpeg", "mpg"),
+    ("htm", "html"),
+    ("yml", "yaml"),
+    ("markdown", "md"),
+];
+
+/// applies the selected unicode normalization form to a string
+fn normalize_unicode(s: &str, form: NormForm) -> String {
+    match form {
+        NormForm::Nfc => s.nfc().collect(),
+        NormForm::Nfd => s.nfd().collect(),
+        NormForm::Nfkc => s.nfkc().collect(),
+        NormForm::Nfkd => s.nfkd().collect(),
+    }
+}
+
+/// runs the renamer over one part of the path and puts the path back together around the result
+///
+/// paths without that part, eg a file with no extension, are left alone
+fn apply_rename_to_part(
+    path: &Path,
+    regex: &Regex,
+    replacement: &str,
+    part: RenamePart,
+) -> PathBuf {
+    let replace = |text: &OsStr| {
+        regex
+            .replace_all(&text.to_string_lossy(), replacement)
+            .to_string()
+    };
+    match part {
+        RenamePart::Filename => match path.file_name() {
+            Some(filename) => path.with_file_name(replace(filename)),
+            None => path.to_path_buf(),
+        },
+        RenamePart::Stem => match (path.file_stem(), path.extension()) {
+            (Some(stem), Some(ext)) => {
+                path.with_file_name(format!("{}.{}", replace(stem), ext.to_string_lossy()))
+            }
+            (Some(stem), None) => path.with_file_name(replace(stem)),
+            (None, _) => path.to_path_buf(),
+        },
+        RenamePart::Extension => match path.extension() {
+            Some(ext) => path.with_extension(replace(ext)),
+            None => path.to_path_buf(),
+        },
+        RenamePart::Parent => match (path.parent(), path.file_name()) {
+            (Some(parent), Some(filename)) => match parent.file_name() {
+                Some(parent_name) => parent.with_file_name(replace(parent_name)).join(filename),
+                None => path.to_path_buf(),
+            },
+            _ => path.to_path_buf(),
+        },
+    }
+}
+
+/// swaps every space in the filename for what `mode` says
+fn encode_spaces(filename: &str, mode: SpaceMode) -> String {
+    let replacement = match mode {
+        SpaceMode::Percent => "%20",
+        SpaceMode::Underscore => "_",
+        SpaceMode::Dash => "-",
+        SpaceMode::Remove => "",
+    };
+    filename.replace(' ', replacement)
+}
+
+/// replaces every match of the regex, or only the `nth` one (counting from 1) when it's set
+///
+/// if there aren't `nth` matches the haystack is returned as it was
+pub fn replace_matches(
+    regex: &Regex,
+    haystack: &str,
+    replacement: &str,
+    nth: Option<usize>,
+) -> String {
+    let Some(nth) = nth else {
+        return regex.replace_all(haystack, replacement).to_string();
+    };
+    let Some(captures) = nth
+        .checked_sub(1)
+        .and_then(|index| regex.captures_iter(haystack).nth(index))
+    else {
+        return haystack.to_string();
+    };
+    let Some(whole) = captures.get(0) else {
+        return haystack.to_string();
+    };
+    let mut result = haystack[..whole.start()].to_string();
+    captures.expand(replacement, &mut result);
+    result.push_str(&haystack[whole.end()..]);
+    result
+}
+
+/// the planned renames, split up by whether they can be applied
+#[derive(Debug, Default)]
+pub struct ChangeSet {
+    /// source -> destination pairs which can be applied
+    pub changes: Vec<(PathBuf, PathBuf)>,
+    /// pairs where the destination already exists, so they'll be skipped
+    pub blocked: Vec<(PathBuf, PathBuf)>,
+    /// pairs (also in `changes`) where the destination filename was shortened to fit
+    pub truncated: Vec<(PathBuf, PathBuf)>,
+    /// pairs where the destination is a reserved name on Windows, these are skipped unless --sanitize is set
+    pub reserved_names: Vec<(PathBuf, PathBuf)>,
+    /// paths left alone because they don't have the audio tags or PDF metadata the template uses
+    pub skipped_no_metadata: Vec<PathBuf>,
+}
+
+impl ChangeSet {
+    /// true when every source is already at its destination, and nothing was held back
+    pub fn is_all_unchanged(&self) -> bool {
+        self.blocked.is_empty()
+            && self.reserved_names.is_empty()
+            && self.changes.iter().all(|(source, dest)| source == dest)
+    }
+
+    /// runs the destination through the filename clean-ups, then files the change in the right list
+    fn add(&mut self, path: PathBuf, dest: PathBuf, config: &Config) {
+        let dest = match dest.file_name() {
+            Some(filename) if config.normalize.is_some() || config.encode_spaces.is_some() => {
+                let filename = match config.normalize {
+                    Some(form) => normalize_unicode(&filename.to_string_lossy(), form),
+                    None => filename.to_string_lossy().to_string(),
+                };
+                match config.encode_spaces {
+                    Some(mode) => dest.with_file_name(encode_spaces(&filename, mode)),
+                    None => dest.with_file_name(filename),
+                }
+            }
+            _ => dest,
+        };
+        let dest = match dest.file_name() {
+            Some(filename) if filename.len() > config.max_filename_length => {
+                match truncate_filename(&dest, config.max_filename_length, config.truncate_marker) {
+                    Some(truncated) => {
+                        self.truncated.push((path.clone(), truncated.clone()));
+                        truncated
+                    }
+                    None => {
+                        eprintln!(
+                            "Can't shorten {dest:?} to {} bytes, leaving {path:?} alone",
+                            config.max_filename_length
+                        );
+                        path.clone()
+                    }
+                }
+            }
+            _ => dest,
+        };
+        let dest = match path != dest && is_windows_reserved(&dest) {
+            true => {
+                self.reserved_names.push((path.clone(), dest.clone()));
+                match config.sanitize {
+                    true => sanitize_reserved(&dest),
+                    false => return,
+                }
+            }
+            false => dest,
+        };
+        match path != dest && destination_taken(&path, &dest) {
+            true => self.blocked.push((path, dest)),
+            false => self.changes.push((path, dest)),
+        }
+    }
+}
+
+/// true if something's already at `dest`, unless it's `source` itself under a differently cased name
+///
+/// on case-insensitive filesystems `a.jpg` exists when checking for `A.jpg`, but renaming it is fine.
+/// symlinks aren't followed, a link pointing at `dest` is still a different entry to `dest`.
+pub fn destination_taken(source: &Path, dest: &Path) -> bool {
+    dest.symlink_metadata().is_ok() && !is_case_only_rename(source, dest)
+}
+
+/// true if `dest` is `source`'s own directory entry, with a name that only differs by case
+///
+/// a hard link in the same directory is the same inode too, but it's listed under its own name
+fn is_case_only_rename(source: &Path, dest: &Path) -> bool {
+    let (Some(source_name), Some(dest_name)) = (source.file_name(), dest.file_name()) else {
+        return false;
+    };
+    source_name != dest_name
+        && source.parent() == dest.parent()
+        && source_name.to_string_lossy().to_lowercase()
+            == dest_name.to_string_lossy().to_lowercase()
+        && same_entry(source, dest)
+        && !is_listed(dest)
+}
+
+/// true if both paths are the same inode, without following symlinks
+#[cfg(unix)]
+fn same_entry(source: &Path, dest: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (source.symlink_metadata(), dest.symlink_metadata()) {
+        (Ok(source), Ok(dest)) => source.dev() == dest.dev() && source.ino() == dest.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_entry(source: &Path, dest: &Path) -> bool {
+    source.symlink_metadata().is_ok() && dest.symlink_metadata().is_ok()
+}
+
+/// true if the directory has an entry with exactly the path's filename
+fn is_listed(path: &Path) -> bool {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    parent.read_dir().is_ok_and(|mut entries| {
+        entries.any(|entry| {
+            entry.is_ok_and(|entry| Some(entry.file_name().as_os_str()) == path.file_name())
+        })
+    })
+}
+
+/// swaps the characters Windows doesn't allow in filenames for `_`, and drops the trailing dots and spaces it strips
+pub(crate) fn windows_safe(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_end_matches(['.', ' '])
+        .to_string()
+}
+
+/// filenames Windows won't let you use, regardless of extension
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// checks if the stem of the filename is one of the reserved names on Windows, case-insensitively
+fn is_windows_reserved(path: &Path) -> bool {
+    match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) => WINDOWS_RESERVED_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(stem)),
+        None => false,
+    }
+}
+
+/// appends an underscore to the stem so it's not a reserved name any more
+fn sanitize_reserved(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{stem}_.{}", ext.to_string_lossy())),
+        None => path.with_file_name(format!("{stem}_")),
+    }
+}
+
+/// shortens the stem of the filename so the whole filename fits in `max_length` bytes, keeping the extension
+///
+/// returns None if it can't be done, eg the extension's too long by itself
+fn truncate_filename(path: &Path, max_length: usize, marker: bool) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let extension = match path.extension() {
+        Some(ext) => format!(".{}", ext.to_string_lossy()),
+        None => String::new(),
+    };
+    let marker = match marker {
+        true => "…",
+        false => "",
+    };
+    let available = max_length.checked_sub(extension.len() + marker.len())?;
+    if available == 0 {
+        return None;
+    }
+
+    // find the longest prefix of the stem that ends on a char boundary
+    let mut cut = available.min(stem.len());
+    while !stem.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    Some(path.with_file_name(format!("{}{marker}{extension}", &stem[..cut])))
+}
+
+/// swaps the extension of a path if it matches `find`, otherwise leaves it alone
+fn rename_extension(path: &Path, find: &str, replace: &str) -> PathBuf {
+    match path.extension() {
+        Some(ext) if ext == find => path.with_extension(replace),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// the stem without `prefix`, None if it doesn't start with it or there'd be nothing left
+fn strip_stem_prefix<'a>(stem: &'a str, prefix: &str) -> Option<&'a str> {
+    stem.strip_prefix(prefix).filter(|stem| !stem.is_empty())
+}
+
+/// the stem without `suffix`, None if it doesn't end with it or there'd be nothing left
+fn strip_stem_suffix<'a>(stem: &'a str, suffix: &str) -> Option<&'a str> {
+    stem.strip_suffix(suffix).filter(|stem| !stem.is_empty())
+}
+
+/// swaps the file's stem for `stem`, keeping the extension
+fn with_stem(path: &Path, stem: &OsStr) -> PathBuf {
+    let mut filename = stem.to_os_string();
+    if let Some(extension) = path.extension() {
+        filename.push(".");
+        filename.push(extension);
+    }
+    path.with_file_name(filename)
+}
+
+/// strips then appends the --strip-* and --append-* strings to the stem, erroring if a strip doesn't fit
+fn edit_stem(path: &Path, config: &Config) -> Result<PathBuf, String> {
+    if !config.edits_stem() {
+        return Ok(path.to_path_buf());
+    }
+    let Some(stem) = path.file_stem() else {
+        return Ok(path.to_path_buf());
+    };
+    let mut stem = stem.to_os_string();
+    if config.strip_prefix.is_some() || config.strip_suffix.is_some() {
+        let Some(mut stripped) = stem.to_str() else {
+            return Err(format!("{stem:?} isn't valid UTF-8"));
+        };
+        if let Some(ref prefix) = config.strip_prefix {
+            stripped = strip_stem_prefix(stripped, prefix)
+                .ok_or_else(|| format!("{stripped:?} doesn't start with {prefix:?}"))?;
+        }
+        if let Some(ref suffix) = config.strip_suffix {
+            stripped = strip_stem_suffix(stripped, suffix)
+                .ok_or_else(|| format!("{stripped:?} doesn't end with {suffix:?}"))?;
+        }
+        stem = OsString::from(stripped);
+    }
+    let mut edited = OsString::from(config.append_prefix.as_deref().unwrap_or_default());
+    edited.push(stem);
+    edited.push(config.append_suffix.as_deref().unwrap_or_default());
+    Ok(with_stem(path, &edited))
+}
+
+/// moves the file into a subdirectory named for the crc32 of its filename modulo `buckets`, in hex
+///
+/// the names are padded to the same width, so 16 buckets are `0` to `f` and 256 are `00` to `ff`
+fn rename_by_hash_bucket(path: &Path, buckets: u32) -> PathBuf {
+    let Some(filename) = path.file_name() else {
+        return path.to_path_buf();
+    };
+    let bucket = crc32fast::hash(filename.as_encoded_bytes()) % buckets;
+    let width = format!("{:x}", buckets.saturating_sub(1)).len();
+    let parent = path.parent().unwrap_or(Path::new(""));
+    parent.join(format!("{bucket:0width$x}")).join(filename)
+}
+
+/// when the file was created, falling back to when it was modified with a warning if the filesystem doesn't know
+///
+/// std reads the birth time through statx on Linux and the platform's birthtime elsewhere
+fn created_or_modified(path: &Path) -> std::io::Result<SystemTime> {
+    let metadata = path.metadata()?;
+    match metadata.created() {
+        Ok(created) => Ok(created),
+        Err(err) => {
+            eprintln!("Warning: couldn't read when {path:?} was created ({err}), using when it was modified");
+            metadata.modified()
+        }
+    }
+}
+
+/// moves the file into `YYYY/MM/DD` subdirectories of `base_path`, from the local date it was modified or created
+fn rename_by_date(path: &Path, base_path: &Path, date_source: DateSource) -> PathBuf {
+    let Some(filename) = path.file_name() else {
+        return path.to_path_buf();
+    };
+    let time = match date_source {
+        DateSource::Modified => path.metadata().and_then(|metadata| metadata.modified()),
+        DateSource::Created => created_or_modified(path),
+    };
+    let time = match time {
+        Ok(time) => time,
+        Err(err) => {
+            eprintln!("Couldn't read the date of {path:?}, leaving it alone: {err:?}");
+            return path.to_path_buf();
+        }
+    };
+    let date = chrono::DateTime::<chrono::Local>::from(time).date_naive();
+    base_path
+        .join(format!("{:04}", date.year()))
+        .join(format!("{:02}", date.month()))
+        .join(format!("{:02}", date.day()))
+        .join(filename)
+}
+
+/// checks the file's magic bytes and gives it the extension for its actual type, if that's different
+fn rename_by_content_type(path: &Path) -> PathBuf {
+    let kind = match infer::get_from_path(path) {
+        Ok(Some(kind)) => kind,
+        Ok(None) => return path.to_path_buf(),
+        Err(err) => {
+            eprintln!("Couldn't read {path:?} to detect its type: {err:?}");
+            return path.to_path_buf();
+        }
+    };
+    let detected = kind.extension();
+    let current = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    // jpeg and jpg are the same thing, for example
+    let same_type = current == detected
+        || EXTENSION_SUGGESTIONS.iter().any(|(long, short)| {
+            (current == *long && detected == *short) || (current == *short && detected == *long)
+        });
+    match same_type {
+        true => path.to_path_buf(),
+        false => path.with_extension(detected),
+    }
+}
+
+/// fills in the rename template for a path, the result's the new filename in the same directory
+///
+/// available variables are `stem`, `ext`, `filename`, `parent` (the directory name), `n` (counter),
+/// and the renamer's capture groups as `cap1`, `cap2`... and by name
+pub fn render_rename_template(
+    path: &Path,
+    relative_path: &str,
+    renamer_regex: &Regex,
+    template: &str,
+    counter: &str,
+    extra_context: HashMap<String, String>,
+) -> Result<PathBuf, String> {
+    let lossy = |value: Option<&OsStr>| value.unwrap_or_default().to_string_lossy().to_string();
+
+    let mut context: HashMap<String, String> = HashMap::from([
+        ("stem".to_string(), lossy(path.file_stem())),
+        ("ext".to_string(), lossy(path.extension())),
+        ("filename".to_string(), lossy(path.file_name())),
+        (
+            "parent".to_string(),
+            lossy(path.parent().and_then(|parent| parent.file_name())),
+        ),
+        ("n".to_string(), counter.to_string()),
+    ]);
+    context.extend(extra_context);
+    if let Some(captures) = renamer_regex.captures(relative_path) {
+        captures
+            .iter()
+            .enumerate()
+            .skip(1)
+            .for_each(|(index, group)| {
+                if let Some(group) = group {
+                    context.insert(format!("cap{index}"), group.as_str().to_string());
+                }
+            });
+        renamer_regex.capture_names().flatten().for_each(|name| {
+            if let Some(group) = captures.name(name) {
+                context.insert(name.to_string(), group.as_str().to_string());
+            }
+        });
+    }
+
+    let filename = template::render_template(template, &context)?;
+    Ok(path.with_file_name(filename))
+}
+
+/// reads one source of template variables from a file, see [audio_tags::read_audio_tags]
+type MetadataReader = fn(&Path) -> Result<HashMap<String, String>, String>;
+
+/// the audio tags and PDF metadata the template can use, or None if they can't be read or any it uses are missing
+pub fn template_metadata(path: &Path, config: &Config) -> Option<HashMap<String, String>> {
+    let mut metadata = HashMap::new();
+    let mut provided: Vec<&str> = Vec::new();
+    let readers: [(bool, &[&str], MetadataReader); 2] = [
+        (
+            config.audio_tags,
+            &audio_tags::AUDIO_TAGS,
+            audio_tags::read_audio_tags,
+        ),
+        (
+            config.pdf_metadata,
+            &pdf_metadata::PDF_METADATA,
+            pdf_metadata::read_pdf_metadata,
+        ),
+    ];
+    for (enabled, names, read) in readers {
+        if !enabled {
+            continue;
+        }
+        match read(path) {
+            Ok(values) => metadata.extend(values),
+            Err(err) => {
+                eprintln!("{err}, leaving it alone");
+                return None;
+            }
+        }
+        provided.extend(names);
+    }
+    template::template_variables(&config.replacement_string)
+        .into_iter()
+        .all(|name| !provided.contains(&name) || metadata.contains_key(name))
+        .then_some(metadata)
+}
+
+/// works out the source -> destination pair for each path in turn, keeping the counter and skipped paths between them
+struct ChangePairIterator<'a, I: Iterator<Item = PathBuf>> {
+    paths: I,
+    base_path: String,
+    /// not needed when renaming in one of the shorthand modes
+    renamer_regex: Option<Regex>,
+    config: &'a Config,
+    /// how many paths have been through so far, for the `n` template variable
+    counter: usize,
+    /// how many paths have been through each directory so far, for --counter-per-dir
+    counters_per_dir: HashMap<PathBuf, usize>,
+    /// paths left alone because they don't have the audio tags or PDF metadata the template uses
+    skipped_no_metadata: Vec<PathBuf>,
+}
+
+impl<'a, I: Iterator<Item = PathBuf>> ChangePairIterator<'a, I> {
+    fn new(
+        paths: impl IntoIterator<IntoIter = I>,
+        base_path: String,
+        renamer_regex: Option<Regex>,
+        config: &'a Config,
+    ) -> Self {
+        ChangePairIterator {
+            paths: paths.into_iter(),
+            base_path,
+            renamer_regex,
+            config,
+            counter: 0,
+            counters_per_dir: HashMap::new(),
+            skipped_no_metadata: Vec::new(),
+        }
+    }
+
+    fn destination(&mut self, path: &Path) -> PathBuf {
+        let config = self.config;
+        if let Some((find, replace)) = &config.rename_extension_only {
+            rename_extension(path, find, replace)
+        } else if config.rename_by_content_type {
+            rename_by_content_type(path)
+        } else if let Some(buckets) = config.hash_buckets {
+            rename_by_hash_bucket(path, buckets)
+        } else if let Some(date_source) = config.date_dirs {
+            rename_by_date(path, Path::new(&self.base_path), date_source)
+        } else if let Some(rules) = &config.ext_map {
+            ext_map::apply_ext_map(path, rules)
+        } else if let (Some(renamer_regex), true) = (&self.renamer_regex, config.match_whole_path) {
+            self.whole_path_destination(path, renamer_regex)
+        } else if let Some(renamer_regex) = &self.renamer_regex {
+            let Some(path_str) = path.to_str() else {
+                eprintln!(
+                    "Skipping {} as its name isn't valid UTF-8",
+                    path.to_string_lossy()
+                );
+                return path.to_path_buf();
+            };
+            if let Some(part) = config.rename_part {
+                return apply_rename_to_part(path, renamer_regex, &config.replacement_string, part);
+            }
+            // paths found from a relative file path aren't under the canonical base path, so use them whole
+            let (prefix, path_str) = match path_str.strip_prefix(&self.base_path) {
+                Some(relative) => (self.base_path.as_str(), relative.to_string()),
+                None => ("", path_str.to_string()),
+            };
+            if config.rename_template {
+                let Some(extra_context) = template_metadata(path, config) else {
+                    self.skipped_no_metadata.push(path.to_path_buf());
+                    return path.to_path_buf();
+                };
+                return match config.counter.format(self.counter - 1).and_then(|counter| {
+                    render_rename_template(
+                        path,
+                        &path_str,
+                        renamer_regex,
+                        &config.replacement_string,
+                        &counter,
+                        extra_context,
+                    )
+                }) {
+                    Ok(dest) => dest,
+                    Err(err) => {
+                        eprintln!(
+                            "Failed to render template for {path:?}, leaving it alone: {err}"
+                        );
+                        path.to_path_buf()
+                    }
+                };
+            }
+            let result = replace_matches(
+                renamer_regex,
+                &path_str,
+                &config.replacement_string,
+                config.replace_nth,
+            );
+
+            PathBuf::from(OsString::from(format!("{prefix}{result}")))
+        } else {
+            path.to_path_buf()
+        }
+    }
+}
+
+impl<I: Iterator<Item = PathBuf>> ChangePairIterator<'_, I> {
+    /// runs the renamer over the canonical path for --match-whole-path, leaving the file alone if the new directory doesn't exist
+    fn whole_path_destination(&self, path: &Path, renamer_regex: &Regex) -> PathBuf {
+        let config = self.config;
+        let canonical = match path.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(err) => {
+                eprintln!("Failed to find the full path of {path:?}, leaving it alone: {err}");
+                return path.to_path_buf();
+            }
+        };
+        let Some(canonical_str) = canonical.to_str() else {
+            eprintln!(
+                "Skipping {} as its name isn't valid UTF-8",
+                path.to_string_lossy()
+            );
+            return path.to_path_buf();
+        };
+        let dest = PathBuf::from(replace_matches(
+            renamer_regex,
+            canonical_str,
+            &config.replacement_string,
+            config.replace_nth,
+        ));
+        if dest == canonical {
+            return path.to_path_buf();
+        }
+        match dest.parent() {
+            Some(parent) if !config.create_dirs && !parent.is_dir() => {
+                eprintln!(
+                    "Not moving {path:?} to {dest:?} as {parent:?} doesn't exist, use --create-dirs to create it"
+                );
+                path.to_path_buf()
+            }
+            _ => dest,
+        }
+    }
+}
+
+impl<I: Iterator<Item = PathBuf>> Iterator for ChangePairIterator<'_, I> {
+    type Item = (PathBuf, PathBuf);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = self.paths.next()?;
+        self.counter = match self.config.counter_per_dir {
+            true => {
+                let parent = path.parent().unwrap_or(Path::new("")).to_path_buf();
+                let counter = self.counters_per_dir.entry(parent).or_default();
+                *counter += 1;
+                *counter
+            }
+            false => self.counter + 1,
+        };
+        let dest = self.destination(&path);
+        let dest = match edit_stem(&dest, self.config) {
+            Ok(dest) => dest,
+            Err(err) => {
+                eprintln!("Warning: {err}, leaving {path:?} alone");
+                path.clone()
+            }
+        };
+        Some((path, dest))
+    }
+}
+
+/// takes the found paths, the base path, renamer regex and config and returns a list of start -> end
+///
+/// the paths are paired up one at a time as they come in, so they can come straight from [crate::get_matched_paths].
+/// the regex isn't needed when only renaming extensions
+pub fn get_change_pairs(
+    paths: impl IntoIterator<Item = PathBuf>,
+    base_path: String,
+    matcher_regex: Option<Regex>,
+    config: &Config,
+) -> ChangeSet {
+    let mut changeset = ChangeSet::default();
+    let mut pairs = ChangePairIterator::new(paths, base_path, matcher_regex, config);
+    pairs
+        .by_ref()
+        .for_each(|(path, dest)| changeset.add(path, dest, config));
+    changeset
+        .skipped_no_metadata
+        .extend(pairs.skipped_no_metadata);
+    changeset
+}
+
+#[cfg(feature = "lua")]
+pub fn get_change_pairs_lua(
+    paths: impl IntoIterator<Item = PathBuf>,
+    script: &str,
+    config: &Config,
+) -> ChangeSet {
+    let mut changeset = ChangeSet::default();
+    paths.into_iter().for_each(|path| {
+        let dest = match lua::apply_lua_rename(script, &path) {
+            Ok(Some(dest)) => dest,
+            Ok(None) => path.clone(),
+            Err(err) => {
+                eprintln!("Lua script failed on {path:?}, leaving it alone: {err}");
+                path.clone()
+            }
+        };
+        changeset.add(path, dest, config);
+    });
+    changeset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_filenames_are_skipped() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new("/photos").join(OsStr::from_bytes(b"\xff.jpeg"));
+        let renamer_regex = Regex::new("(jpeg)").unwrap();
+        for rename_template in [false, true] {
+            let config = Config {
+                replacement_string: "jpg".to_string(),
+                rename_template,
+                ..Default::default()
+            };
+            let mut pairs = ChangePairIterator::new(
+                vec![path.clone()],
+                "/photos/".to_string(),
+                Some(renamer_regex.clone()),
+                &config,
+            );
+            assert_eq!(pairs.next(), Some((path.clone(), path.clone())));
+            assert_eq!(pairs.next(), None);
+        }
+    }
+
+    #[test]
+    fn case_only_rename_is_not_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("Foo.txt");
+        std::fs::write(&source, "").unwrap();
+        // foo.txt is either missing or, on a case-insensitive filesystem, Foo.txt itself
+        assert!(!destination_taken(&source, &dir.path().join("foo.txt")));
+    }
+
+    #[test]
+    fn existing_destination_is_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.txt");
+        let dest = dir.path().join("b.txt");
+        std::fs::write(&source, "").unwrap();
+        assert!(!destination_taken(&source, &dest));
+        std::fs::write(&dest, "").unwrap();
+        assert!(destination_taken(&source, &dest));
+    }
+
+    #[test]
+    fn case_only_rename_needs_the_same_directory_and_a_name_differing_by_case() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("Foo.txt");
+        std::fs::write(&source, "").unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        assert!(!is_case_only_rename(&source, &source));
+        assert!(!is_case_only_rename(
+            &source,
+            &dir.path().join("sub/foo.txt")
+        ));
+        assert!(!is_case_only_rename(&source, &dir.path().join("bar.txt")));
+        assert!(!is_case_only_rename(
+            &source,
+            &dir.path().join("Foo.txt.bak")
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_to_the_destination_is_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.lnk");
+        let dest = dir.path().join("b.txt");
+        std::fs::write(&dest, "").unwrap();
+        std::os::unix::fs::symlink(&dest, &source).unwrap();
+        assert!(!is_case_only_rename(&source, &dest));
+        assert!(destination_taken(&source, &dest));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dangling_symlink_at_the_destination_is_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.txt");
+        let dest = dir.path().join("b.lnk");
+        std::fs::write(&source, "").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("missing"), &dest).unwrap();
+        assert!(destination_taken(&source, &dest));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_link_to_the_source_is_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.txt");
+        let dest = dir.path().join("b.txt");
+        std::fs::write(&source, "").unwrap();
+        std::fs::hard_link(&source, &dest).unwrap();
+        assert!(!is_case_only_rename(&source, &dest));
+        assert!(destination_taken(&source, &dest));
+    }
+
+    // a case-sensitive filesystem, so a hard link can have the source's name in another case
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn hard_link_differing_by_case_is_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("Foo.txt");
+        let dest = dir.path().join("foo.txt");
+        std::fs::write(&source, "").unwrap();
+        std::fs::hard_link(&source, &dest).unwrap();
+        assert!(!is_case_only_rename(&source, &dest));
+        assert!(destination_taken(&source, &dest));
+    }
+
+    #[test]
+    fn truncated_names_keep_their_extension() {
+        let path = Path::new("/photos/holiday.jpeg");
+        assert_eq!(
+            truncate_filename(path, 9, false),
+            Some(PathBuf::from("/photos/holi.jpeg"))
+        );
+        assert_eq!(
+            truncate_filename(path, 12, true),
+            Some(PathBuf::from("/photos/holi….jpeg"))
+        );
+        assert_eq!(truncate_filename(path, 5, false), None);
+    }
+
+    #[test]
+    fn truncation_stays_on_a_char_boundary() {
+        assert_eq!(
+            truncate_filename(Path::new("/photos/café"), 4, false),
+            Some(PathBuf::from("/photos/caf"))
+        );
+    }
+
+    #[test]
+    fn spaces_are_encoded() {
+        let name = "holiday photo 1.jpeg";
+        assert_eq!(
+            encode_spaces(name, SpaceMode::Percent),
+            "holiday%20photo%201.jpeg"
+        );
+        assert_eq!(
+            encode_spaces(name, SpaceMode::Underscore),
+            "holiday_photo_1.jpeg"
+        );
+        assert_eq!(encode_spaces(name, SpaceMode::Dash), "holiday-photo-1.jpeg");
+        assert_eq!(encode_spaces(name, SpaceMode::Remove), "holidayphoto1.jpeg");
+    }
+
+    #[test]
+    fn every_match_is_replaced_by_default() {
+        let regex = Regex::new("(a)").unwrap();
+        assert_eq!(
+            replace_matches(&regex, "banana", "[$1]", None),
+            "b[a]n[a]n[a]"
+        );
+    }
+
+    #[test]
+    fn only_the_nth_match_is_replaced() {
+        let regex = Regex::new("(a)").unwrap();
+        assert_eq!(
+            replace_matches(&regex, "banana", "[$1]", Some(2)),
+            "ban[a]na"
+        );
+        assert_eq!(replace_matches(&regex, "banana", "[$1]", Some(4)), "banana");
+        assert_eq!(replace_matches(&regex, "banana", "[$1]", Some(0)), "banana");
+    }
+}
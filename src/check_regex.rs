@@ -0,0 +1,209 @@
+//! The `check-regex` subcommand, which validates a pattern and explains what it matches.
+
+use regex::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind, Look};
+
+/// describes a character class, eg "any character except newline" or "one of [a-z]"
+fn describe_class(class: &Class) -> String {
+    let ranges: Vec<(u32, u32)> = match class {
+        Class::Unicode(class) => class
+            .ranges()
+            .iter()
+            .map(|range| (range.start() as u32, range.end() as u32))
+            .collect(),
+        Class::Bytes(class) => class
+            .ranges()
+            .iter()
+            .map(|range| (range.start() as u32, range.end() as u32))
+            .collect(),
+    };
+    let max = match class {
+        Class::Unicode(_) => char::MAX as u32,
+        Class::Bytes(_) => u8::MAX as u32,
+    };
+    let show = |c: u32| match char::from_u32(c) {
+        Some(c) if !c.is_control() => c.to_string(),
+        _ => format!("\\x{{{c:x}}}"),
+    };
+
+    match ranges.as_slice() {
+        [] => "nothing (empty class)".to_string(),
+        [(0, end)] if *end == max => "any character".to_string(),
+        [(0, 9), (11, end)] if *end == max => "any character except newline".to_string(),
+        [(start, end)] if start == end => format!("the character '{}'", show(*start)),
+        ranges if ranges.len() > 10 => format!("one of {} character ranges", ranges.len()),
+        ranges => format!(
+            "one of [{}]",
+            ranges
+                .iter()
+                .map(|(start, end)| match start == end {
+                    true => show(*start),
+                    false => format!("{}-{}", show(*start), show(*end)),
+                })
+                .collect::<String>()
+        ),
+    }
+}
+
+fn describe_look(look: &Look) -> String {
+    match look {
+        Look::Start => "start-of-input".to_string(),
+        Look::End => "end-of-input".to_string(),
+        Look::StartLF | Look::StartCRLF => "start-of-line".to_string(),
+        Look::EndLF | Look::EndCRLF => "end-of-line".to_string(),
+        Look::WordAscii | Look::WordUnicode => "a word boundary".to_string(),
+        Look::WordAsciiNegate | Look::WordUnicodeNegate => "not a word boundary".to_string(),
+        other => format!("a {other:?} assertion"),
+    }
+}
+
+/// turns a parsed regex into an english description
+pub fn describe_hir(hir: &Hir) -> String {
+    match hir.kind() {
+        HirKind::Empty => "nothing (the empty string)".to_string(),
+        HirKind::Literal(literal) => {
+            format!("literal '{}'", String::from_utf8_lossy(&literal.0))
+        }
+        HirKind::Class(class) => describe_class(class),
+        HirKind::Look(look) => describe_look(look),
+        HirKind::Repetition(repetition) => {
+            let count = match (repetition.min, repetition.max) {
+                (0, None) => "zero or more times (*)".to_string(),
+                (1, None) => "one or more times (+)".to_string(),
+                (0, Some(1)) => "optionally (?)".to_string(),
+                (min, None) => format!("at least {min} times"),
+                (min, Some(max)) if min == max => format!("exactly {min} times"),
+                (min, Some(max)) => format!("between {min} and {max} times"),
+            };
+            let lazy = match repetition.greedy {
+                true => "",
+                false => ", as few as possible",
+            };
+            format!("{}, repeated {count}{lazy}", describe_hir(&repetition.sub))
+        }
+        HirKind::Capture(capture) => {
+            let name = match capture.name {
+                Some(ref name) => format!(" '{name}'"),
+                None => String::new(),
+            };
+            format!(
+                "capture group {}{name} of ({})",
+                capture.index,
+                describe_hir(&capture.sub)
+            )
+        }
+        HirKind::Concat(hirs) => hirs
+            .iter()
+            .map(describe_hir)
+            .collect::<Vec<String>>()
+            .join(", then "),
+        HirKind::Alternation(hirs) => format!(
+            "either {}",
+            hirs.iter()
+                .map(|hir| format!("({})", describe_hir(hir)))
+                .collect::<Vec<String>>()
+                .join(" or ")
+        ),
+    }
+}
+
+/// the parsed regex as an indented tree, one node per line, eg `Literal('jpeg')` under a `Concat`
+pub fn hir_tree(hir: &Hir, depth: usize) -> String {
+    let label = match hir.kind() {
+        HirKind::Empty => "Empty".to_string(),
+        HirKind::Literal(literal) => {
+            format!("Literal('{}')", String::from_utf8_lossy(&literal.0))
+        }
+        HirKind::Class(class) => format!("Class({})", describe_class(class)),
+        HirKind::Look(look) => format!("Look({})", describe_look(look)),
+        HirKind::Repetition(repetition) => {
+            let max = match repetition.max {
+                Some(max) => max.to_string(),
+                None => "∞".to_string(),
+            };
+            let lazy = match repetition.greedy {
+                true => "",
+                false => ", lazy",
+            };
+            format!("Repetition({}..{max}{lazy})", repetition.min)
+        }
+        HirKind::Capture(capture) => match capture.name {
+            Some(ref name) => format!("Capture({}, '{name}')", capture.index),
+            None => format!("Capture({})", capture.index),
+        },
+        HirKind::Concat(_) => "Concat".to_string(),
+        HirKind::Alternation(_) => "Alternation".to_string(),
+    };
+    let mut tree = format!("{}{label}\n", "  ".repeat(depth));
+    match hir.kind() {
+        HirKind::Repetition(repetition) => tree.push_str(&hir_tree(&repetition.sub, depth + 1)),
+        HirKind::Capture(capture) => tree.push_str(&hir_tree(&capture.sub, depth + 1)),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs
+            .iter()
+            .for_each(|hir| tree.push_str(&hir_tree(hir, depth + 1))),
+        _ => {}
+    }
+    tree
+}
+
+/// validates the pattern and prints what it does, returns false if it's not valid
+///
+/// with `tree` set, the parsed regex is printed as a tree too
+pub fn check_regex(pattern: &str, tree: bool) -> bool {
+    let regex = match Regex::new(pattern) {
+        Ok(regex) => regex,
+        Err(err) => {
+            eprintln!("Invalid regex: {err}");
+            return false;
+        }
+    };
+    println!("Valid regex: {pattern}");
+
+    match regex_syntax::Parser::new().parse(pattern) {
+        Ok(hir) => {
+            println!("This matches: {}.", describe_hir(&hir));
+            if tree {
+                print!("{}", hir_tree(&hir, 0));
+            }
+        }
+        Err(err) => eprintln!("Couldn't explain the regex: {err}"),
+    }
+
+    let groups = regex.captures_len() - 1;
+    let names: Vec<&str> = regex.capture_names().flatten().collect();
+    match names.is_empty() {
+        true => println!("Capture groups: {groups}"),
+        false => println!("Capture groups: {groups} (named: {})", names.join(", ")),
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(pattern: &str) -> String {
+        describe_hir(&regex_syntax::Parser::new().parse(pattern).unwrap())
+    }
+
+    #[test]
+    fn literals_and_anchors_are_described() {
+        assert_eq!(describe(r"\.jpeg$"), "literal '.jpeg', then end-of-input");
+    }
+
+    #[test]
+    fn repetitions_and_groups_are_described() {
+        assert_eq!(
+            describe("(?<year>[0-9]{4})"),
+            "capture group 1 'year' of (one of [0-9], repeated exactly 4 times)"
+        );
+        assert_eq!(
+            describe(".*?"),
+            "any character except newline, repeated zero or more times (*), as few as possible"
+        );
+        assert_eq!(
+            describe("jpe?g|png"),
+            "either (literal 'jp', then literal 'e', repeated optionally (?), then literal 'g') or (literal 'png')"
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! `--color-scheme`, named colour themes for the preview table headers and the prompts.
+
+use dialoguer::console::{style, Style};
+use dialoguer::theme::ColorfulTheme;
+use prettytable::color;
+use renamer::config::ColorScheme;
+
+/// the colours a scheme's made of, `prompt`, `value` and `active` are 256-colour terminal codes
+struct Palette {
+    header: color::Color,
+    prompt: u8,
+    value: u8,
+    active: u8,
+}
+
+fn palette(scheme: ColorScheme) -> Palette {
+    match scheme {
+        ColorScheme::Solarized => Palette {
+            header: color::YELLOW,
+            prompt: 136,
+            value: 33,
+            active: 37,
+        },
+        ColorScheme::Dracula => Palette {
+            header: color::MAGENTA,
+            prompt: 212,
+            value: 84,
+            active: 141,
+        },
+        ColorScheme::Nord => Palette {
+            header: color::CYAN,
+            prompt: 110,
+            value: 109,
+            active: 67,
+        },
+        ColorScheme::Monokai => Palette {
+            header: color::BRIGHT_RED,
+            prompt: 208,
+            value: 148,
+            active: 197,
+        },
+    }
+}
+
+/// the colour for the preview table's titles
+pub fn header_color(scheme: ColorScheme) -> color::Color {
+    palette(scheme).header
+}
+
+/// the default dialoguer theme with its colours swapped for the scheme's
+fn theme(scheme: ColorScheme) -> ColorfulTheme {
+    let palette = palette(scheme);
+    let colored = |color: u8| Style::new().for_stderr().color256(color);
+    let prefix = |text: &str, color: u8| style(text.to_string()).for_stderr().color256(color);
+    ColorfulTheme {
+        defaults_style: colored(palette.active),
+        prompt_prefix: prefix("?", palette.prompt),
+        success_prefix: prefix("✔", palette.value),
+        values_style: colored(palette.value),
+        active_item_style: colored(palette.active),
+        active_item_prefix: prefix("❯", palette.value),
+        checked_item_prefix: prefix("✔", palette.value),
+        picked_item_prefix: prefix("❯", palette.value),
+        ..ColorfulTheme::default()
+    }
+}
+/// the theme for the prompts, dialoguer's own colours when there's no scheme
+pub fn dialoguer_theme(scheme: Option<ColorScheme>) -> ColorfulTheme {
+    scheme.map_or_else(ColorfulTheme::default, theme)
+}
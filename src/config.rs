@@ -0,0 +1,1016 @@
+//! Everything that controls finding and renaming files, built with [Config::builder].
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use glob::Pattern;
+use regex::Regex;
+
+/// what to do when a destination already exists, see `--rename-conflicts`
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ConflictStrategy {
+    /// leave the source alone
+    #[default]
+    Skip,
+    /// replace the existing destination
+    Overwrite,
+    /// add _1, _2 etc (see --conflict-suffix-format) to the destination's stem until it's free
+    Suffix,
+    /// stop applying changes
+    Error,
+}
+
+/// which kinds of entries to match, see `--match-type`
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum MatchType {
+    /// regular files
+    #[default]
+    File,
+    /// directories
+    Dir,
+    /// symlinks themselves, not what they point to
+    Symlink,
+    /// anything
+    All,
+}
+
+impl MatchType {
+    /// checks the path's own type, without following symlinks
+    pub fn matches(self, path: &Path) -> bool {
+        let file_type = match (self, path.symlink_metadata()) {
+            (MatchType::All, _) => return true,
+            (_, Ok(metadata)) => metadata.file_type(),
+            (_, Err(_)) => return false,
+        };
+        match self {
+            MatchType::File => file_type.is_file(),
+            MatchType::Dir => file_type.is_dir(),
+            MatchType::Symlink => file_type.is_symlink(),
+            MatchType::All => true,
+        }
+    }
+}
+
+/// a unicode normalization form for destination filenames, see `--normalize`
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum NormForm {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+/// what to swap spaces in destination filenames for, see `--encode-spaces`
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum SpaceMode {
+    /// %20
+    Percent,
+    /// _
+    Underscore,
+    /// -
+    Dash,
+    /// take them out
+    Remove,
+}
+
+/// which part of the path --rename-part runs the renamer over
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum RenamePart {
+    /// the whole filename
+    Filename,
+    /// the filename without the extension
+    Stem,
+    /// the extension, without the dot
+    Extension,
+    /// the name of the directory the file's in
+    Parent,
+}
+
+/// which of a file's times --rename-by-modified-date and --rename-by-created-date use
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateSource {
+    Modified,
+    Created,
+}
+
+/// how `{{n}}` counts in templates, from --counter-start, --counter-step and --counter-width
+#[derive(Clone, Copy, Debug)]
+pub struct CounterFormat {
+    pub start: usize,
+    pub step: usize,
+    /// zero-pad to this many digits
+    pub width: usize,
+    /// from --format-number, used instead of `width` when it's set
+    pub number_format: Option<NumberFormat>,
+}
+
+impl Default for CounterFormat {
+    fn default() -> Self {
+        CounterFormat {
+            start: 1,
+            step: 1,
+            width: 0,
+            number_format: None,
+        }
+    }
+}
+
+impl CounterFormat {
+    /// the counter for the `index`th path, counting from 0, or an error if it's too big to count to
+    pub fn format(&self, index: usize) -> Result<String, String> {
+        let n = index
+            .checked_mul(self.step)
+            .and_then(|offset| offset.checked_add(self.start))
+            .ok_or_else(|| {
+                format!(
+                    "the counter overflowed at {} + {index} * {}, try a smaller --counter-start or --counter-step",
+                    self.start, self.step
+                )
+            })?;
+        Ok(match self.number_format {
+            Some(ref number_format) => format_number(n, number_format),
+            None => format!("{n:0width$}", width = self.width),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum NumberBase {
+    Decimal,
+    LowerHex,
+    UpperHex,
+    Octal,
+    Binary,
+}
+
+/// a printf-like specifier for --format-number, eg `%d`, `%04x` or `%8b`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NumberFormat {
+    base: NumberBase,
+    /// pad to this many characters
+    width: usize,
+    /// pad with zeroes instead of spaces
+    zero_pad: bool,
+}
+
+/// a printf-style specifier, an optional 0 for zero padding, the width, then the base
+static NUMBER_SPECIFIER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^%(0)?([0-9]+)?([dxXob])$").unwrap());
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let captures = NUMBER_SPECIFIER.captures(value).ok_or(format!(
+            "expected something like %d, %04x, %X, %o or %b, got {value}"
+        ))?;
+        let width = match captures.get(2) {
+            Some(width) => width
+                .as_str()
+                .parse()
+                .map_err(|err| format!("invalid width in {value}: {err}"))?,
+            None => 0,
+        };
+        let base = match &captures[3] {
+            "x" => NumberBase::LowerHex,
+            "X" => NumberBase::UpperHex,
+            "o" => NumberBase::Octal,
+            "b" => NumberBase::Binary,
+            _ => NumberBase::Decimal,
+        };
+        Ok(NumberFormat {
+            base,
+            width,
+            zero_pad: captures.get(1).is_some(),
+        })
+    }
+}
+
+/// formats `n` the way a printf specifier like `%04x` would
+pub fn format_number(n: usize, fmt: &NumberFormat) -> String {
+    let width = fmt.width;
+    match (fmt.base, fmt.zero_pad) {
+        (NumberBase::Decimal, true) => format!("{n:0width$}"),
+        (NumberBase::Decimal, false) => format!("{n:width$}"),
+        (NumberBase::LowerHex, true) => format!("{n:0width$x}"),
+        (NumberBase::LowerHex, false) => format!("{n:width$x}"),
+        (NumberBase::UpperHex, true) => format!("{n:0width$X}"),
+        (NumberBase::UpperHex, false) => format!("{n:width$X}"),
+        (NumberBase::Octal, true) => format!("{n:0width$o}"),
+        (NumberBase::Octal, false) => format!("{n:width$o}"),
+        (NumberBase::Binary, true) => format!("{n:0width$b}"),
+        (NumberBase::Binary, false) => format!("{n:width$b}"),
+    }
+}
+
+/// how to show the preview of changes, see `--preview-format`
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum PreviewFormat {
+    /// a table, paged if it's long
+    #[default]
+    Table,
+    /// one `source → destination` per line
+    List,
+    /// a JSON array of changes
+    Json,
+    /// comma-separated values with a header row
+    Csv,
+}
+
+/// a column in the preview table, see `--preview-cols`
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum PreviewColumn {
+    /// the current path
+    Source,
+    /// the new path, with the differences highlighted
+    Dest,
+    /// the file's size
+    Size,
+    /// when the file was last modified
+    Modified,
+    /// the new extension
+    Ext,
+    /// ✓ if the path changes, ✗ if it doesn't
+    Changed,
+}
+
+/// a named colour theme for the preview table headers and the prompts, see `--color-scheme`
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ColorScheme {
+    Solarized,
+    Dracula,
+    Nord,
+    Monokai,
+}
+
+/// the `{n}`, `{n:3}` or `{n:03}` in a --conflict-suffix-format, compiled once as it's used for every candidate name
+static CONFLICT_SUFFIX_PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{n(?::(0)?([0-9]+))?\}").unwrap());
+
+/// fills in `{n}` in a --conflict-suffix-format, `{n:3}` pads it to 3 wide with spaces and `{n:03}` with zeroes
+pub fn format_conflict_suffix(template: &str, n: usize) -> String {
+    CONFLICT_SUFFIX_PLACEHOLDER
+        .replace_all(template, |captures: &regex::Captures| {
+            let width = captures
+                .get(2)
+                .and_then(|width| width.as_str().parse().ok())
+                .unwrap_or(0);
+            match captures.get(1) {
+                Some(_) => format!("{n:0width$}"),
+                None => format!("{n:width$}"),
+            }
+        })
+        .to_string()
+}
+
+/// anchors the matcher to the end of the path, if it isn't already
+fn anchor_matcher(matcher_string: &str) -> String {
+    match matcher_string.ends_with('$') {
+        true => matcher_string.to_string(),
+        false => format!("{matcher_string}$"),
+    }
+}
+
+type PatternChars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// copies characters up to and including the first one in `ends`
+fn copy_until(chars: &mut PatternChars, result: &mut String, ends: &[char]) {
+    for c in chars.by_ref() {
+        result.push(c);
+        if ends.contains(&c) {
+            break;
+        }
+    }
+}
+
+/// copies the rest of a character class, including any nested classes
+fn copy_class(chars: &mut PatternChars, result: &mut String) {
+    // a ] straight after the [ or [^ is a literal
+    if let Some(c) = chars.next_if_eq(&'^') {
+        result.push(c);
+    }
+    if let Some(c) = chars.next_if_eq(&']') {
+        result.push(c);
+    }
+    let mut depth = 1;
+    while let Some(c) = chars.next() {
+        result.push(c);
+        match c {
+            '\\' => result.extend(chars.next()),
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// makes each ASCII letter in the pattern match either case, eg `jpeg` becomes `[jJ][pP][eE][gG]`
+///
+/// escapes, character classes, repetitions and group names and flags are left alone
+fn expand_case_variants(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                result.push(c);
+                if let Some(escaped) = chars.next() {
+                    result.push(escaped);
+                    // \p{Greek}, \x{1F600} and friends
+                    if matches!(escaped, 'p' | 'P' | 'x' | 'u' | 'U') && chars.peek() == Some(&'{')
+                    {
+                        copy_until(&mut chars, &mut result, &['}']);
+                    }
+                }
+            }
+            '[' => {
+                result.push(c);
+                copy_class(&mut chars, &mut result);
+            }
+            '{' => {
+                result.push(c);
+                copy_until(&mut chars, &mut result, &['}']);
+            }
+            '(' if chars.peek() == Some(&'?') => {
+                result.push(c);
+                copy_until(&mut chars, &mut result, &[':', ')', '>']);
+            }
+            c if c.is_ascii_alphabetic() => {
+                result.push_str(&format!(
+                    "[{}{}]",
+                    c.to_ascii_lowercase(),
+                    c.to_ascii_uppercase()
+                ));
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// what to match and how to rename it, along with how the changes are previewed and applied
+#[derive(Clone)]
+pub struct Config {
+    pub matcher_string: String,
+    pub renamer_string: String,
+    pub replacement_string: String,
+    pub show_unchanged: bool,
+    pub match_type: MatchType,
+    pub simulate: bool,
+    pub rename_conflicts: ConflictStrategy,
+    /// see [format_conflict_suffix]
+    pub conflict_suffix_format: String,
+    /// only overwrite destinations which are older than the source
+    pub rename_if_newer: bool,
+    pub backup_suffix: Option<String>,
+    pub backup_dir: Option<PathBuf>,
+    pub backup_preserve_structure: bool,
+    pub preserve_timestamps: bool,
+    pub normalize: Option<NormForm>,
+    pub encode_spaces: Option<SpaceMode>,
+    pub use_pager: bool,
+    pub scroll_preview: bool,
+    /// (find, replace) extensions when only renaming extensions
+    pub rename_extension_only: Option<(String, String)>,
+    /// (matcher, renamer, replacement) rules from --ext-map
+    pub ext_map: Option<Vec<(Regex, Regex, String)>>,
+    /// contents of the --script-file
+    #[cfg(feature = "lua")]
+    pub lua_script: Option<String>,
+    pub max_filename_length: usize,
+    pub truncate_marker: bool,
+    pub fail_fast: bool,
+    /// stop applying after this many failed renames
+    pub max_errors: Option<usize>,
+    pub match_depth: Option<usize>,
+    /// print each path considered while matching, and whether it matched
+    pub verbose: bool,
+    /// keep printing them when there's more than [crate::matching::VERBOSE_FILE_LIMIT]
+    pub verbose_all: bool,
+    /// exit if there's any errors while scanning for files, instead of skipping over them
+    pub strict: bool,
+    /// after matching, list how many of each extension weren't matched
+    pub summarize_unmatched: bool,
+    pub preview_limit: usize,
+    /// how many matched paths to list, None lists all of them
+    pub preview_count: Option<usize>,
+    pub sanitize: bool,
+    pub journal: Option<PathBuf>,
+    pub output_rename_log: Option<PathBuf>,
+    pub rename_conflict_log: Option<PathBuf>,
+    pub rename_by_content_type: bool,
+    /// added to the start and end of destination stems, see `edit_stem`
+    pub append_prefix: Option<String>,
+    pub append_suffix: Option<String>,
+    /// taken off the start and end of destination stems, see `edit_stem`
+    pub strip_prefix: Option<String>,
+    pub strip_suffix: Option<String>,
+    /// the stem edits are the whole rename, so there's no renamer regex
+    pub affixes_only: bool,
+    /// how many subdirectories to spread files across, see `rename_by_hash_bucket`
+    pub hash_buckets: Option<u32>,
+    /// move files into dated subdirectories of the base path, see `rename_by_date`
+    pub date_dirs: Option<DateSource>,
+    /// the replacement string is a template, see `render_rename_template`
+    pub rename_template: bool,
+    /// the template can use audio tags, see `read_audio_tags`
+    pub audio_tags: bool,
+    /// the template can use PDF metadata, see `read_pdf_metadata`
+    pub pdf_metadata: bool,
+    pub exclude_globs: Vec<Pattern>,
+    pub counter: CounterFormat,
+    /// restart the counter for each directory
+    pub counter_per_dir: bool,
+    /// only match paths modified after this, for --rename-incremental
+    pub modified_after: Option<std::time::SystemTime>,
+    /// canonical path of the --only-in directory
+    pub only_in: Option<PathBuf>,
+    pub match_case_variants: bool,
+    /// the matcher is literal text, not a regex
+    pub escape_special: bool,
+    /// put around the matcher and renamer before they're compiled
+    pub wrap_regex_prefix: String,
+    pub wrap_regex_suffix: String,
+    /// the renamer runs over the canonical path, and the result's used as an absolute path
+    pub match_whole_path: bool,
+    /// create missing parent directories of destinations when applying
+    pub create_dirs: bool,
+    /// only replace this match of the renamer, see `replace_matches`
+    pub replace_nth: Option<usize>,
+    /// the part of the path the renamer runs over, see `apply_rename_to_part`
+    pub rename_part: Option<RenamePart>,
+    /// match with this query instead of the matcher regex, see `fuzzy_score`
+    pub fuzzy_match: Option<String>,
+    pub fuzzy_threshold: f64,
+    /// keep the paths the matcher doesn't match
+    pub invert_match: bool,
+    /// allow any number of capture groups in the renamer
+    pub skip_regex_validation: bool,
+    pub preview_format: PreviewFormat,
+    pub preview_cols: Vec<PreviewColumn>,
+    pub color_scheme: Option<ColorScheme>,
+    /// stdout is carrying machine-readable output, so [crate::status] prints go to stderr
+    pub status_to_stderr: bool,
+}
+
+impl Config {
+    /// the matcher as it's actually run, escaped with --escape-special, wrapped with --wrap-regex-*, anchored and with --match-case-variants applied
+    pub fn matcher_pattern(&self) -> String {
+        let matcher_string = match self.escape_special {
+            true => regex::escape(&self.matcher_string),
+            false => self.matcher_string.clone(),
+        };
+        let matcher_string = match self.match_case_variants {
+            true => expand_case_variants(&matcher_string),
+            false => matcher_string,
+        };
+        anchor_matcher(&format!(
+            "{}{matcher_string}{}",
+            self.wrap_regex_prefix, self.wrap_regex_suffix
+        ))
+    }
+
+    /// the renamer as it's actually run, with --wrap-regex-prefix and --wrap-regex-suffix around it
+    pub fn renamer_pattern(&self) -> String {
+        format!(
+            "{}{}{}",
+            self.wrap_regex_prefix, self.renamer_string, self.wrap_regex_suffix
+        )
+    }
+
+    /// what to do when a destination exists, --rename-if-newer-than-dest overwrites the ones that are older
+    pub fn conflict_strategy(&self) -> ConflictStrategy {
+        match self.rename_if_newer {
+            true => ConflictStrategy::Overwrite,
+            false => self.rename_conflicts,
+        }
+    }
+
+    /// false when the renames come from a shorthand mode instead of the matcher/renamer regexes
+    pub fn uses_regexes(&self) -> bool {
+        self.rename_extension_only.is_none() && self.ext_map.is_none()
+    }
+
+    /// true when any of --append-prefix, --append-suffix, --strip-prefix or --strip-suffix are set
+    pub(crate) fn edits_stem(&self) -> bool {
+        self.append_prefix.is_some()
+            || self.append_suffix.is_some()
+            || self.strip_prefix.is_some()
+            || self.strip_suffix.is_some()
+    }
+
+    /// false when the renamer regex and replacement aren't needed, eg a script's doing the renaming
+    pub fn uses_renamer_regex(&self) -> bool {
+        if self.rename_by_content_type
+            || self.hash_buckets.is_some()
+            || self.date_dirs.is_some()
+            || self.affixes_only
+        {
+            return false;
+        }
+        #[cfg(feature = "lua")]
+        if self.lua_script.is_some() {
+            return false;
+        }
+        self.uses_regexes()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            matcher_string: r".*\.jpeg$".to_string(),
+            renamer_string: "(jpeg)".to_string(),
+            replacement_string: "jpg".to_string(),
+            show_unchanged: true,
+            match_type: MatchType::File,
+            simulate: false,
+            rename_conflicts: ConflictStrategy::Skip,
+            conflict_suffix_format: "_{n}".to_string(),
+            rename_if_newer: false,
+            backup_suffix: None,
+            backup_dir: None,
+            backup_preserve_structure: false,
+            preserve_timestamps: false,
+            normalize: None,
+            encode_spaces: None,
+            use_pager: true,
+            scroll_preview: false,
+            rename_extension_only: None,
+            ext_map: None,
+            #[cfg(feature = "lua")]
+            lua_script: None,
+            max_filename_length: 255,
+            truncate_marker: false,
+            fail_fast: false,
+            max_errors: None,
+            match_depth: None,
+            verbose: false,
+            verbose_all: false,
+            strict: false,
+            summarize_unmatched: false,
+            preview_limit: 50,
+            preview_count: Some(10),
+            sanitize: false,
+            journal: None,
+            output_rename_log: None,
+            rename_conflict_log: None,
+            rename_by_content_type: false,
+            hash_buckets: None,
+            date_dirs: None,
+            append_prefix: None,
+            append_suffix: None,
+            strip_prefix: None,
+            strip_suffix: None,
+            affixes_only: false,
+            rename_template: false,
+            audio_tags: false,
+            pdf_metadata: false,
+            exclude_globs: Vec::new(),
+            counter: CounterFormat::default(),
+            counter_per_dir: false,
+            modified_after: None,
+            only_in: None,
+            match_case_variants: false,
+            escape_special: false,
+            wrap_regex_prefix: String::new(),
+            wrap_regex_suffix: String::new(),
+            match_whole_path: false,
+            create_dirs: false,
+            replace_nth: None,
+            rename_part: None,
+            fuzzy_match: None,
+            fuzzy_threshold: 0.5,
+            invert_match: false,
+            skip_regex_validation: false,
+            preview_format: PreviewFormat::Table,
+            preview_cols: vec![PreviewColumn::Source, PreviewColumn::Dest],
+            color_scheme: None,
+            status_to_stderr: false,
+        }
+    }
+}
+
+/// builds a [Config] without having to know all the fields, see [ConfigBuilder::build] for what's checked
+#[derive(Default)]
+pub struct ConfigBuilder {
+    config: Config,
+    exclude_globs: Vec<String>,
+}
+
+impl Config {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+impl ConfigBuilder {
+    pub fn matcher(mut self, matcher: &str) -> Self {
+        self.config.matcher_string = matcher.to_string();
+        self
+    }
+
+    pub fn renamer(mut self, renamer: &str) -> Self {
+        self.config.renamer_string = renamer.to_string();
+        self
+    }
+
+    pub fn replacement(mut self, replacement: &str) -> Self {
+        self.config.replacement_string = replacement.to_string();
+        self
+    }
+
+    pub fn status_to_stderr(mut self, status_to_stderr: bool) -> Self {
+        self.config.status_to_stderr = status_to_stderr;
+        self
+    }
+
+    pub fn show_unchanged(mut self, show_unchanged: bool) -> Self {
+        self.config.show_unchanged = show_unchanged;
+        self
+    }
+
+    pub fn match_type(mut self, match_type: MatchType) -> Self {
+        self.config.match_type = match_type;
+        self
+    }
+
+    pub fn simulate(mut self, simulate: bool) -> Self {
+        self.config.simulate = simulate;
+        self
+    }
+
+    pub fn rename_conflicts(mut self, rename_conflicts: ConflictStrategy) -> Self {
+        self.config.rename_conflicts = rename_conflicts;
+        self
+    }
+
+    pub fn conflict_suffix_format(mut self, conflict_suffix_format: &str) -> Self {
+        self.config.conflict_suffix_format = conflict_suffix_format.to_string();
+        self
+    }
+
+    pub fn rename_if_newer(mut self, rename_if_newer: bool) -> Self {
+        self.config.rename_if_newer = rename_if_newer;
+        self
+    }
+
+    pub fn backup_suffix(mut self, backup_suffix: Option<String>) -> Self {
+        self.config.backup_suffix = backup_suffix;
+        self
+    }
+
+    pub fn backup_dir(mut self, backup_dir: Option<PathBuf>, preserve_structure: bool) -> Self {
+        self.config.backup_dir = backup_dir;
+        self.config.backup_preserve_structure = preserve_structure;
+        self
+    }
+
+    pub fn preserve_timestamps(mut self, preserve_timestamps: bool) -> Self {
+        self.config.preserve_timestamps = preserve_timestamps;
+        self
+    }
+
+    pub fn normalize(mut self, normalize: Option<NormForm>) -> Self {
+        self.config.normalize = normalize;
+        self
+    }
+
+    pub fn encode_spaces(mut self, encode_spaces: Option<SpaceMode>) -> Self {
+        self.config.encode_spaces = encode_spaces;
+        self
+    }
+
+    pub fn use_pager(mut self, use_pager: bool) -> Self {
+        self.config.use_pager = use_pager;
+        self
+    }
+
+    pub fn scroll_preview(mut self, scroll_preview: bool) -> Self {
+        self.config.scroll_preview = scroll_preview;
+        self
+    }
+
+    /// only swap the `find` extension for `replace`
+    pub fn rename_extension_only(mut self, find: &str, replace: &str) -> Self {
+        self.config.rename_extension_only = Some((find.to_string(), replace.to_string()));
+        self
+    }
+
+    pub fn ext_map(mut self, rules: Vec<(Regex, Regex, String)>) -> Self {
+        self.config.ext_map = Some(rules);
+        self
+    }
+
+    #[cfg(feature = "lua")]
+    pub fn lua_script(mut self, script: String) -> Self {
+        self.config.lua_script = Some(script);
+        self
+    }
+
+    pub fn max_filename_length(mut self, max_filename_length: usize) -> Self {
+        self.config.max_filename_length = max_filename_length;
+        self
+    }
+
+    pub fn truncate_marker(mut self, truncate_marker: bool) -> Self {
+        self.config.truncate_marker = truncate_marker;
+        self
+    }
+
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.config.fail_fast = fail_fast;
+        self
+    }
+
+    pub fn max_errors(mut self, max_errors: Option<usize>) -> Self {
+        self.config.max_errors = max_errors;
+        self
+    }
+
+    pub fn match_depth(mut self, match_depth: Option<usize>) -> Self {
+        self.config.match_depth = match_depth;
+        self
+    }
+
+    /// print each path considered while matching, `verbose_all` keeps going past [crate::matching::VERBOSE_FILE_LIMIT]
+    pub fn verbose(mut self, verbose: bool, verbose_all: bool) -> Self {
+        self.config.verbose = verbose;
+        self.config.verbose_all = verbose_all;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    pub fn summarize_unmatched(mut self, summarize_unmatched: bool) -> Self {
+        self.config.summarize_unmatched = summarize_unmatched;
+        self
+    }
+
+    pub fn preview_limit(mut self, preview_limit: usize) -> Self {
+        self.config.preview_limit = preview_limit;
+        self
+    }
+
+    pub fn preview_count(mut self, preview_count: Option<usize>) -> Self {
+        self.config.preview_count = preview_count;
+        self
+    }
+
+    pub fn sanitize(mut self, sanitize: bool) -> Self {
+        self.config.sanitize = sanitize;
+        self
+    }
+
+    pub fn journal(mut self, journal: Option<PathBuf>) -> Self {
+        self.config.journal = journal;
+        self
+    }
+
+    pub fn output_rename_log(mut self, output_rename_log: Option<PathBuf>) -> Self {
+        self.config.output_rename_log = output_rename_log;
+        self
+    }
+
+    pub fn rename_conflict_log(mut self, rename_conflict_log: Option<PathBuf>) -> Self {
+        self.config.rename_conflict_log = rename_conflict_log;
+        self
+    }
+
+    pub fn rename_by_content_type(mut self, rename_by_content_type: bool) -> Self {
+        self.config.rename_by_content_type = rename_by_content_type;
+        self
+    }
+
+    pub fn append_to_stem(mut self, prefix: Option<String>, suffix: Option<String>) -> Self {
+        self.config.append_prefix = prefix;
+        self.config.append_suffix = suffix;
+        self
+    }
+
+    pub fn strip_from_stem(mut self, prefix: Option<String>, suffix: Option<String>) -> Self {
+        self.config.strip_prefix = prefix;
+        self.config.strip_suffix = suffix;
+        self
+    }
+
+    /// when there's no renamer, replacement or template, any stem edits are the whole rename
+    pub fn affixes_only(mut self, affixes_only: bool) -> Self {
+        self.config.affixes_only = affixes_only;
+        self
+    }
+
+    pub fn hash_buckets(mut self, hash_buckets: Option<u32>) -> Self {
+        self.config.hash_buckets = hash_buckets;
+        self
+    }
+
+    pub fn date_dirs(mut self, date_dirs: Option<DateSource>) -> Self {
+        self.config.date_dirs = date_dirs;
+        self
+    }
+
+    /// treat the replacement as a template, see `render_rename_template`
+    pub fn rename_template(mut self, rename_template: bool) -> Self {
+        self.config.rename_template = rename_template;
+        self
+    }
+
+    pub fn audio_tags(mut self, audio_tags: bool) -> Self {
+        self.config.audio_tags = audio_tags;
+        self
+    }
+
+    pub fn pdf_metadata(mut self, pdf_metadata: bool) -> Self {
+        self.config.pdf_metadata = pdf_metadata;
+        self
+    }
+
+    pub fn counter(mut self, counter: CounterFormat, per_dir: bool) -> Self {
+        self.config.counter = counter;
+        self.config.counter_per_dir = per_dir;
+        self
+    }
+
+    /// skip paths matching this glob, it's parsed in [ConfigBuilder::build]
+    pub fn exclude_glob(mut self, exclude_glob: &str) -> Self {
+        self.exclude_globs.push(exclude_glob.to_string());
+        self
+    }
+
+    pub fn modified_after(mut self, modified_after: Option<std::time::SystemTime>) -> Self {
+        self.config.modified_after = modified_after;
+        self
+    }
+
+    /// only match paths under this directory, it should be canonical
+    pub fn only_in(mut self, only_in: Option<PathBuf>) -> Self {
+        self.config.only_in = only_in;
+        self
+    }
+
+    pub fn escape_special(mut self, escape_special: bool) -> Self {
+        self.config.escape_special = escape_special;
+        self
+    }
+
+    pub fn wrap_regex(mut self, prefix: &str, suffix: &str) -> Self {
+        self.config.wrap_regex_prefix = prefix.to_string();
+        self.config.wrap_regex_suffix = suffix.to_string();
+        self
+    }
+
+    pub fn match_whole_path(mut self, match_whole_path: bool) -> Self {
+        self.config.match_whole_path = match_whole_path;
+        self
+    }
+
+    pub fn create_dirs(mut self, create_dirs: bool) -> Self {
+        self.config.create_dirs = create_dirs;
+        self
+    }
+
+    pub fn replace_nth(mut self, replace_nth: Option<usize>) -> Self {
+        self.config.replace_nth = replace_nth;
+        self
+    }
+
+    pub fn rename_part(mut self, rename_part: Option<RenamePart>) -> Self {
+        self.config.rename_part = rename_part;
+        self
+    }
+
+    /// make the matcher's letters match either case, see `expand_case_variants`
+    pub fn match_case_variants(mut self, match_case_variants: bool) -> Self {
+        self.config.match_case_variants = match_case_variants;
+        self
+    }
+
+    /// match paths with a fuzzy query scoring above the threshold, instead of the matcher regex
+    pub fn fuzzy_match(mut self, query: Option<String>, threshold: f64) -> Self {
+        self.config.fuzzy_match = query;
+        self.config.fuzzy_threshold = threshold;
+        self
+    }
+
+    pub fn invert_match(mut self, invert_match: bool) -> Self {
+        self.config.invert_match = invert_match;
+        self
+    }
+
+    pub fn skip_regex_validation(mut self, skip_regex_validation: bool) -> Self {
+        self.config.skip_regex_validation = skip_regex_validation;
+        self
+    }
+
+    pub fn preview_format(mut self, preview_format: PreviewFormat) -> Self {
+        self.config.preview_format = preview_format;
+        self
+    }
+
+    pub fn preview_cols(mut self, preview_cols: Vec<PreviewColumn>) -> Self {
+        self.config.preview_cols = preview_cols;
+        self
+    }
+
+    pub fn color_scheme(mut self, color_scheme: Option<ColorScheme>) -> Self {
+        self.config.color_scheme = color_scheme;
+        self
+    }
+
+    /// checks the matcher and renamer regexes and exclude globs parse, and that there's room for a filename
+    pub fn build(self) -> Result<Config, String> {
+        let mut config = self.config;
+        Regex::new(&config.matcher_pattern())
+            .map_err(|err| format!("Invalid matcher regex: {err}"))?;
+        Regex::new(&config.renamer_pattern())
+            .map_err(|err| format!("Invalid renamer regex: {err}"))?;
+        if !(0.0..=1.0).contains(&config.fuzzy_threshold) {
+            return Err("The fuzzy match threshold has to be between 0 and 1".to_string());
+        }
+        if format_conflict_suffix(&config.conflict_suffix_format, 1)
+            == format_conflict_suffix(&config.conflict_suffix_format, 2)
+        {
+            return Err("The conflict suffix format needs {n} in it somewhere".to_string());
+        }
+        config.affixes_only &= config.edits_stem();
+        if config.hash_buckets == Some(0) {
+            return Err("There has to be at least 1 hash bucket".to_string());
+        }
+        if config.replace_nth == Some(0) {
+            return Err("Matches are counted from 1 for --regex-replace-nth".to_string());
+        }
+        if config.max_filename_length == 0 {
+            return Err("The maximum filename length has to be more than 0".to_string());
+        }
+        for exclude_glob in self.exclude_globs.iter() {
+            let pattern = Pattern::new(exclude_glob)
+                .map_err(|err| format!("Invalid exclude glob {exclude_glob}: {err}"))?;
+            config.exclude_globs.push(pattern);
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_overflow_is_an_error() {
+        let counter = CounterFormat {
+            start: usize::MAX - 1,
+            step: 1,
+            ..Default::default()
+        };
+        assert_eq!(counter.format(1), Ok(usize::MAX.to_string()));
+        assert!(counter.format(2).is_err());
+        let counter = CounterFormat {
+            step: usize::MAX,
+            ..Default::default()
+        };
+        assert!(counter.format(2).is_err());
+    }
+
+    #[test]
+    fn letters_match_either_case() {
+        assert_eq!(expand_case_variants(r"\.jpe?g"), r"\.[jJ][pP][eE]?[gG]");
+    }
+
+    #[test]
+    fn regex_syntax_is_left_alone() {
+        assert_eq!(
+            expand_case_variants(r"(?<ext>\p{Greek}[a-z]{2,3})\b"),
+            r"(?<ext>\p{Greek}[a-z]{2,3})\b"
+        );
+    }
+
+    #[test]
+    fn conflict_suffix_is_padded() {
+        assert_eq!(format_conflict_suffix("_{n}", 7), "_7");
+        assert_eq!(format_conflict_suffix(" ({n:3})", 7), " (  7)");
+        assert_eq!(format_conflict_suffix("-{n:03}", 7), "-007");
+        assert_eq!(format_conflict_suffix("-copy", 7), "-copy");
+    }
+
+    #[test]
+    fn numbers_are_formatted_like_printf() {
+        let format = |spec: &str, n| format_number(n, &spec.parse().unwrap());
+        assert_eq!(format("%d", 42), "42");
+        assert_eq!(format("%04x", 255), "00ff");
+        assert_eq!(format("%4X", 255), "  FF");
+        assert_eq!(format("%o", 8), "10");
+        assert_eq!(format("%08b", 5), "00000101");
+        assert!("%q".parse::<NumberFormat>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Finding renames which go round in a circle, eg `a` → `b` and `b` → `a`, which can't be done one rename at a time.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// a set of source -> destination renames to be applied together
+pub struct RenamePlan<'a> {
+    pub changes: &'a [(PathBuf, PathBuf)],
+}
+
+/// the paths in a cycle, in rename order, the last one gets renamed to the first
+#[derive(Debug, PartialEq)]
+pub struct Cycle {
+    pub paths: Vec<PathBuf>,
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let paths: Vec<String> = self
+            .paths
+            .iter()
+            .chain(self.paths.first())
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        write!(f, "{}", paths.join(" → "))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// follows each rename as an edge in a graph with a depth-first search, returning each cycle found once
+pub fn detect_cycles(plans: &[RenamePlan]) -> Vec<Cycle> {
+    let mut edges: HashMap<&PathBuf, Vec<&PathBuf>> = HashMap::new();
+    // keep the order things were found in, so the report is stable
+    let mut sources: Vec<&PathBuf> = Vec::new();
+    plans
+        .iter()
+        .flat_map(|plan| plan.changes.iter())
+        .filter(|(source, dest)| source != dest)
+        .for_each(|(source, dest)| {
+            if !edges.contains_key(source) {
+                sources.push(source);
+            }
+            edges.entry(source).or_default().push(dest);
+        });
+
+    let mut visits: HashMap<&PathBuf, Visit> = HashMap::new();
+    let mut cycles = Vec::new();
+    for start in sources {
+        if visits.contains_key(start) {
+            continue;
+        }
+        // (node, index of the next edge to follow)
+        let mut stack: Vec<(&PathBuf, usize)> = vec![(start, 0)];
+        visits.insert(start, Visit::InProgress);
+        while let Some((node, next_edge)) = stack.pop() {
+            let Some(dest) = edges.get(node).and_then(|dests| dests.get(next_edge)) else {
+                visits.insert(node, Visit::Done);
+                continue;
+            };
+            stack.push((node, next_edge + 1));
+            match visits.get(dest) {
+                None => {
+                    visits.insert(dest, Visit::InProgress);
+                    stack.push((dest, 0));
+                }
+                Some(Visit::InProgress) => {
+                    // everything on the stack from `dest` onwards is the cycle
+                    let from = stack
+                        .iter()
+                        .position(|(path, _)| path == dest)
+                        .unwrap_or_default();
+                    cycles.push(Cycle {
+                        paths: stack[from..]
+                            .iter()
+                            .map(|(path, _)| (*path).clone())
+                            .collect(),
+                    });
+                }
+                Some(Visit::Done) => {}
+            }
+        }
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(pairs: &[(&str, &str)]) -> Vec<(PathBuf, PathBuf)> {
+        pairs
+            .iter()
+            .map(|(source, dest)| (PathBuf::from(source), PathBuf::from(dest)))
+            .collect()
+    }
+
+    #[test]
+    fn swaps_and_rotations_are_cycles() {
+        let changes = changes(&[("a", "b"), ("b", "a"), ("x", "y"), ("y", "z"), ("z", "x")]);
+        let cycles = detect_cycles(&[RenamePlan { changes: &changes }]);
+        assert_eq!(cycles.len(), 2);
+        assert_eq!(cycles[0].to_string(), "a → b → a");
+        assert_eq!(cycles[1].to_string(), "x → y → z → x");
+    }
+
+    #[test]
+    fn chains_and_unchanged_paths_are_not_cycles() {
+        let changes = changes(&[("a", "a"), ("b", "c"), ("c", "d")]);
+        assert!(detect_cycles(&[RenamePlan { changes: &changes }]).is_empty());
+    }
+
+    #[test]
+    fn cycles_can_span_plans() {
+        let first = changes(&[("a", "b")]);
+        let second = changes(&[("b", "a")]);
+        let cycles = detect_cycles(&[
+            RenamePlan { changes: &first },
+            RenamePlan { changes: &second },
+        ]);
+        assert_eq!(
+            cycles,
+            vec![Cycle {
+                paths: vec![PathBuf::from("a"), PathBuf::from("b")]
+            }]
+        );
+    }
+}
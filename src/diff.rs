@@ -0,0 +1,131 @@
+//! Character-level diff of filenames, for highlighting what a rename changes.
+
+use console::Style;
+
+#[derive(Debug, PartialEq)]
+enum DiffOp {
+    Same(char),
+    Insert(char),
+    Delete(char),
+}
+
+/// simple longest-common-subsequence diff between two strings, character by character
+fn diff_chars(source: &[char], dest: &[char]) -> Vec<DiffOp> {
+    // lcs[i][j] is the LCS length of source[i..] and dest[j..]
+    let mut lcs = vec![vec![0usize; dest.len() + 1]; source.len() + 1];
+    for i in (0..source.len()).rev() {
+        for j in (0..dest.len()).rev() {
+            lcs[i][j] = match source[i] == dest[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(source.len().max(dest.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < source.len() && j < dest.len() {
+        if source[i] == dest[j] {
+            ops.push(DiffOp::Same(source[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            ops.push(DiffOp::Delete(source[i]));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(dest[j]));
+            j += 1;
+        }
+    }
+    ops.extend(source[i..].iter().map(|c| DiffOp::Delete(*c)));
+    ops.extend(dest[j..].iter().map(|c| DiffOp::Insert(*c)));
+    ops
+}
+
+/// renders the source with unchanged characters in grey and the ones the rename removes in red
+///
+/// without colours there's nothing to show the difference with, so it's just the source
+pub fn highlight_deletions(source: &str, dest: &str) -> String {
+    if !console::colors_enabled() {
+        return source.to_string();
+    }
+    let same = Style::new().dim();
+    let delete = Style::new().red().strikethrough();
+    render(source, dest, |op| match op {
+        DiffOp::Same(c) => Some(same.apply_to(c).to_string()),
+        DiffOp::Delete(c) => Some(delete.apply_to(c).to_string()),
+        DiffOp::Insert(_) => None,
+    })
+}
+
+/// renders the destination with unchanged characters in grey and the ones the rename adds in green
+///
+/// without colours it's just the destination
+pub fn diff_highlight(source: &str, dest: &str) -> String {
+    if !console::colors_enabled() {
+        return dest.to_string();
+    }
+    let same = Style::new().dim();
+    let insert = Style::new().green();
+    render(source, dest, |op| match op {
+        DiffOp::Same(c) => Some(same.apply_to(c).to_string()),
+        DiffOp::Insert(c) => Some(insert.apply_to(c).to_string()),
+        DiffOp::Delete(_) => None,
+    })
+}
+
+fn render(source: &str, dest: &str, style: impl Fn(DiffOp) -> Option<String>) -> String {
+    let source: Vec<char> = source.chars().collect();
+    let dest: Vec<char> = dest.chars().collect();
+    diff_chars(&source, &dest)
+        .into_iter()
+        .filter_map(style)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_finds_the_changed_characters() {
+        let source: Vec<char> = "a.jpeg".chars().collect();
+        let dest: Vec<char> = "a.jpg".chars().collect();
+        assert_eq!(
+            diff_chars(&source, &dest),
+            vec![
+                DiffOp::Same('a'),
+                DiffOp::Same('.'),
+                DiffOp::Same('j'),
+                DiffOp::Same('p'),
+                DiffOp::Delete('e'),
+                DiffOp::Same('g'),
+            ]
+        );
+    }
+
+    #[test]
+    fn each_side_only_has_its_own_characters() {
+        let plain = |op| match op {
+            DiffOp::Same(c) | DiffOp::Insert(c) => Some(c.to_string()),
+            DiffOp::Delete(_) => None,
+        };
+        assert_eq!(
+            render("/photos/photo.jpeg", "/photos/photo.jpg", plain),
+            "/photos/photo.jpg"
+        );
+    }
+
+    #[test]
+    fn plain_names_without_colours() {
+        console::set_colors_enabled(false);
+        assert_eq!(
+            diff_highlight("/photos/photo.jpeg", "/photos/photo.jpg"),
+            "/photos/photo.jpg"
+        );
+        assert_eq!(
+            highlight_deletions("/photos/photo.jpeg", "/photos/photo.jpg"),
+            "/photos/photo.jpeg"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! `--dry-run-diff`, which prints the renames as a unified diff of the directory listings, so they can
+//! be reviewed with the usual diff tools.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// the filename, or the whole path if it doesn't have one
+fn listing_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// a hunk for each directory with the directory as context, the old names as deletions and the new
+/// names as insertions, moves show up as a deletion in one directory and an insertion in the other
+pub fn write_dry_run_diff(
+    changes: &[(PathBuf, PathBuf)],
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    let mut listings: BTreeMap<&Path, (Vec<String>, Vec<String>)> = BTreeMap::new();
+    for (source, dest) in changes.iter().filter(|(source, dest)| source != dest) {
+        listings
+            .entry(source.parent().unwrap_or(Path::new("")))
+            .or_default()
+            .0
+            .push(listing_name(source));
+        listings
+            .entry(dest.parent().unwrap_or(Path::new("")))
+            .or_default()
+            .1
+            .push(listing_name(dest));
+    }
+    for (parent, (removed, added)) in listings {
+        let parent = parent.to_string_lossy().replace('\\', "/");
+        // like git, the a/ and b/ prefixes go on a relative path
+        let header = parent.trim_start_matches('/');
+        writeln!(writer, "--- a/{header}")?;
+        writeln!(writer, "+++ b/{header}")?;
+        writeln!(
+            writer,
+            "@@ -1,{} +1,{} @@",
+            removed.len() + 1,
+            added.len() + 1
+        )?;
+        writeln!(writer, " {parent}/")?;
+        for name in removed {
+            writeln!(writer, "-{name}")?;
+        }
+        for name in added {
+            writeln!(writer, "+{name}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_directory_gets_a_hunk() {
+        let changes = vec![
+            (
+                PathBuf::from("/photos/a.jpeg"),
+                PathBuf::from("/photos/a.jpg"),
+            ),
+            (
+                PathBuf::from("/photos/b.jpeg"),
+                PathBuf::from("/archive/b.jpeg"),
+            ),
+            (
+                PathBuf::from("/photos/c.jpg"),
+                PathBuf::from("/photos/c.jpg"),
+            ),
+        ];
+        let mut output = Vec::new();
+        write_dry_run_diff(&changes, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "--- a/archive\n+++ b/archive\n@@ -1,1 +1,2 @@\n /archive/\n+b.jpeg\n\
+             --- a/photos\n+++ b/photos\n@@ -1,3 +1,2 @@\n /photos/\n-a.jpeg\n-b.jpeg\n+a.jpg\n"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Extension renaming rules from `--ext-map`, eg `jpeg:jpg,JPEG:jpg`
+
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+#[derive(Debug)]
+pub struct ExtMap {
+    /// (from, to) extension pairs, in the order they were given
+    pairs: Vec<(String, String)>,
+}
+
+impl ExtMap {
+    /// parses a comma-separated list of `from:to` pairs
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let pairs = input
+            .split(',')
+            .filter(|pair| !pair.trim().is_empty())
+            .map(|pair| match pair.trim().split_once(':') {
+                Some((from, to)) if !from.is_empty() && !to.is_empty() => Ok((
+                    from.trim_start_matches('.').to_string(),
+                    to.trim_start_matches('.').to_string(),
+                )),
+                _ => Err(format!(
+                    "Invalid extension mapping '{pair}', expected from:to"
+                )),
+            })
+            .collect::<Result<Vec<(String, String)>, String>>()?;
+        if pairs.is_empty() {
+            return Err("No extension mappings given".to_string());
+        }
+        Ok(ExtMap { pairs })
+    }
+
+    /// a matcher string which matches any of the extensions being mapped from
+    pub fn matcher_string(&self) -> String {
+        let extensions: Vec<String> = self
+            .pairs
+            .iter()
+            .map(|(from, _)| regex::escape(from))
+            .collect();
+        format!(r".*\.({})$", extensions.join("|"))
+    }
+
+    /// (matcher, renamer, replacement) for each mapping
+    pub fn rules(&self) -> Result<Vec<(Regex, Regex, String)>, regex::Error> {
+        self.pairs
+            .iter()
+            .map(|(from, to)| {
+                let from = regex::escape(from);
+                Ok((
+                    Regex::new(&format!(r".*\.{from}$"))?,
+                    Regex::new(&format!(r"\.({from})$"))?,
+                    format!(".{to}"),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// runs the rules in order, the first one whose matcher matches the path does the rename
+pub fn apply_ext_map(path: &Path, rules: &[(Regex, Regex, String)]) -> PathBuf {
+    let path_str = path.to_string_lossy();
+    rules
+        .iter()
+        .find(|(matcher, _, _)| matcher.is_match(&path_str))
+        .map(|(_, renamer, replacement)| {
+            PathBuf::from(
+                renamer
+                    .replace(&path_str, regex::NoExpand(replacement))
+                    .to_string(),
+            )
+        })
+        .unwrap_or(path.to_path_buf())
+}
@@ -0,0 +1,124 @@
+//! Translates a subset of `fd` command-line arguments into renamer's matcher configuration.
+
+use std::fmt::Display;
+
+use renamer::config::MatchType;
+
+#[derive(Debug)]
+pub enum FdArgError {
+    /// A flag that needs a value was at the end of the arguments
+    MissingValue(String),
+    /// A `--type` that we can't handle
+    UnsupportedType(String),
+    /// A flag we don't know how to translate
+    UnknownArgument(String),
+}
+
+impl Display for FdArgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FdArgError::MissingValue(arg) => write!(f, "Missing value for fd argument {arg}"),
+            FdArgError::UnsupportedType(val) => {
+                write!(
+                    f,
+                    "Unsupported fd --type '{val}', only 'f'/'file', 'd'/'directory' and 'l'/'symlink' are supported"
+                )
+            }
+            FdArgError::UnknownArgument(arg) => write!(f, "Unknown fd argument '{arg}'"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FdArgs {
+    pub extensions: Vec<String>,
+    /// the --type, if there was one
+    pub match_type: Option<MatchType>,
+}
+
+/// parses fd-style arguments, eg `--type f --extension jpeg`
+pub fn parse_fd_args(args: &[&str]) -> Result<FdArgs, FdArgError> {
+    let mut result = FdArgs::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "-e" | "--extension" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| FdArgError::MissingValue(arg.to_string()))?;
+                result
+                    .extensions
+                    .push(value.trim_start_matches('.').to_string());
+            }
+            "-t" | "--type" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| FdArgError::MissingValue(arg.to_string()))?;
+                match *value {
+                    "f" | "file" => result.match_type = Some(MatchType::File),
+                    "d" | "directory" => result.match_type = Some(MatchType::Dir),
+                    "l" | "symlink" => result.match_type = Some(MatchType::Symlink),
+                    _ => return Err(FdArgError::UnsupportedType(value.to_string())),
+                }
+            }
+            _ => return Err(FdArgError::UnknownArgument(arg.to_string())),
+        }
+    }
+    Ok(result)
+}
+
+/// turns fd-style arguments into the equivalent matcher regex string
+pub fn translate_fd_args(args: &[&str]) -> Result<String, FdArgError> {
+    let fd_args = parse_fd_args(args)?;
+
+    let extensions: Vec<String> = fd_args
+        .extensions
+        .iter()
+        .map(|ext| regex::escape(ext))
+        .collect();
+
+    Ok(match extensions.len() {
+        0 => ".*$".to_string(),
+        1 => format!(r".*\.{}$", extensions[0]),
+        _ => format!(r".*\.({})$", extensions.join("|")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions_become_an_anchored_alternation() {
+        assert_eq!(translate_fd_args(&[]).unwrap(), ".*$");
+        assert_eq!(translate_fd_args(&["-e", "jpeg"]).unwrap(), r".*\.jpeg$");
+        assert_eq!(
+            translate_fd_args(&["--extension", ".jpeg", "-e", "tar.gz"]).unwrap(),
+            r".*\.(jpeg|tar\.gz)$"
+        );
+    }
+
+    #[test]
+    fn type_sets_the_match_type() {
+        let fd_args = parse_fd_args(&["--type", "d", "-e", "jpeg"]).unwrap();
+        assert_eq!(fd_args.match_type, Some(MatchType::Dir));
+        assert_eq!(fd_args.extensions, vec!["jpeg"]);
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert!(matches!(
+            translate_fd_args(&["-e"]),
+            Err(FdArgError::MissingValue(_))
+        ));
+        assert!(matches!(
+            translate_fd_args(&["-t", "x"]),
+            Err(FdArgError::UnsupportedType(_))
+        ));
+        assert!(matches!(
+            translate_fd_args(&["--hidden"]),
+            Err(FdArgError::UnknownArgument(_))
+        ));
+    }
+}
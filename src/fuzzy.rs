@@ -0,0 +1,26 @@
+//! `--fuzzy-match`, scoring paths against a loose query instead of matching the file-matching regex.
+
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+
+/// how well `haystack` matches each space-separated term in `query`, from 0 (a term's missing) to 1
+///
+/// each term's scored against how it'd score matching itself exactly, then they're averaged
+pub fn fuzzy_score(query: &str, haystack: &str) -> f64 {
+    let matcher = SkimMatcherV2::default();
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = terms
+        .iter()
+        .map(|term| {
+            let best = matcher.fuzzy_match(term, term).unwrap_or(0);
+            match matcher.fuzzy_match(haystack, term) {
+                Some(score) if best > 0 => (score as f64 / best as f64).clamp(0.0, 1.0),
+                _ => 0.0,
+            }
+        })
+        .sum();
+    total / terms.len() as f64
+}
@@ -0,0 +1,119 @@
+//! `--interactive-regex-history`, up and down in the regex prompts go through what was entered before,
+//! which is kept in `history.toml` in the state directory between sessions.
+//!
+//! ```toml
+//! matcher = ['.*\.jpeg$', '.*\.png$']
+//! renamer = ['(jpeg)']
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use dialoguer::History;
+
+/// how many entries to keep for each prompt
+const MAX_ENTRIES: usize = 100;
+
+/// which prompt the history is for
+#[derive(Clone, Copy, Debug)]
+pub enum HistoryField {
+    Matcher,
+    Renamer,
+}
+
+impl HistoryField {
+    fn key(self) -> &'static str {
+        match self {
+            HistoryField::Matcher => "matcher",
+            HistoryField::Renamer => "renamer",
+        }
+    }
+}
+
+/// the entries for each prompt, newest first
+pub struct RegexHistory {
+    /// None if there's nowhere to keep it, then it only lasts for this session
+    history_file: Option<PathBuf>,
+    entries: BTreeMap<String, Vec<String>>,
+}
+
+impl RegexHistory {
+    /// reads the history from previous sessions, starting afresh with a warning if it can't be read
+    pub fn load() -> Self {
+        let history_file = crate::incremental::state_dir().map(|dir| dir.join("history.toml"));
+        let entries = match history_file
+            .as_ref()
+            .map(|history_file| (history_file, std::fs::read_to_string(history_file)))
+        {
+            Some((history_file, Ok(contents))) => match toml::from_str(&contents) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    eprintln!("Warning: ignoring the history in {history_file:?}: {err}");
+                    BTreeMap::new()
+                }
+            },
+            Some((_, Err(err))) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Some((history_file, Err(err))) => {
+                eprintln!("Warning: couldn't read the history in {history_file:?}: {err}");
+                BTreeMap::new()
+            }
+            None => BTreeMap::new(),
+        };
+        RegexHistory {
+            history_file,
+            entries,
+        }
+    }
+
+    /// the history for one of the prompts, to pass to [dialoguer::Input::history_with]
+    pub fn field(&mut self, field: HistoryField) -> FieldHistory<'_> {
+        FieldHistory {
+            history: self,
+            field,
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(ref history_file) = self.history_file else {
+            return Ok(());
+        };
+        if let Some(parent) = history_file.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create {parent:?}: {err}"))?;
+        }
+        let contents = toml::to_string(&self.entries)
+            .map_err(|err| format!("Failed to serialize the history: {err}"))?;
+        std::fs::write(history_file, contents)
+            .map_err(|err| format!("Failed to write {history_file:?}: {err}"))
+    }
+}
+
+pub struct FieldHistory<'a> {
+    history: &'a mut RegexHistory,
+    field: HistoryField,
+}
+
+impl History<String> for FieldHistory<'_> {
+    fn read(&self, pos: usize) -> Option<String> {
+        self.history
+            .entries
+            .get(self.field.key())?
+            .get(pos)
+            .cloned()
+    }
+
+    /// moves the value to the front, and saves the history straight away so it's kept if renamer's killed
+    fn write(&mut self, val: &String) {
+        let entries = self
+            .history
+            .entries
+            .entry(self.field.key().to_string())
+            .or_default();
+        entries.retain(|entry| entry != val);
+        entries.insert(0, val.clone());
+        entries.truncate(MAX_ENTRIES);
+        if let Err(err) = self.history.save() {
+            eprintln!("Warning: {err}");
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! `--rename-incremental`, remembering when renamer was last applied with the same arguments so
+//! later runs only look at files modified since.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// where the last run's time is kept for this set of arguments
+pub struct Incremental {
+    state_file: PathBuf,
+    /// None on the first run, when everything's processed
+    pub last_run: Option<SystemTime>,
+    /// when this run started, it's what gets recorded so files changed while it's running are picked up next time
+    started: SystemTime,
+}
+
+/// `$XDG_STATE_HOME/renamer`, or `~/.local/state/renamer`
+pub fn state_dir() -> Option<PathBuf> {
+    let state_dir = match std::env::var_os("XDG_STATE_HOME") {
+        Some(state_dir) => PathBuf::from(state_dir),
+        None => PathBuf::from(std::env::var_os("HOME")?)
+            .join(".local")
+            .join("state"),
+    };
+    Some(state_dir.join("renamer"))
+}
+
+impl Incremental {
+    /// reads the last run's time for these arguments (without the program name), if there's been one
+    pub fn load(args: &[String]) -> Result<Self, String> {
+        let session_hash = crc32fast::hash(args.join("\0").as_bytes());
+        let state_file = state_dir()
+            .ok_or("Couldn't work out where to keep the last run time, is $HOME set?")?
+            .join(format!("{session_hash:08x}"))
+            .join("last_run");
+        let last_run = match std::fs::read_to_string(&state_file) {
+            Ok(contents) => {
+                let seconds: u64 = contents
+                    .trim()
+                    .parse()
+                    .map_err(|err| format!("Couldn't read the time in {state_file:?}: {err}"))?;
+                Some(UNIX_EPOCH + Duration::from_secs(seconds))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(format!("Couldn't read {state_file:?}: {err}")),
+        };
+        Ok(Incremental {
+            state_file,
+            last_run,
+            started: SystemTime::now(),
+        })
+    }
+
+    /// saves when this run started as the last run's time
+    pub fn record(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.state_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let seconds = self
+            .started
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        std::fs::write(&self.state_file, format!("{seconds}\n"))
+    }
+}
@@ -0,0 +1,93 @@
+//! Write-ahead journal of renames, so if the process dies part way through you can see where it got to.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Serializer};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum JournalEntry<'a> {
+    /// written before the rename is attempted
+    Pending {
+        #[serde(serialize_with = "lossy")]
+        source: &'a Path,
+        #[serde(serialize_with = "lossy")]
+        dest: &'a Path,
+        timestamp: u64,
+    },
+    Done {
+        #[serde(serialize_with = "lossy")]
+        source: &'a Path,
+        #[serde(serialize_with = "lossy")]
+        dest: &'a Path,
+    },
+    Failed {
+        #[serde(serialize_with = "lossy")]
+        source: &'a Path,
+        #[serde(serialize_with = "lossy")]
+        dest: &'a Path,
+        error: String,
+    },
+}
+
+/// paths are written as text with anything that isn't UTF-8 replaced, so they can't stop an entry being written
+fn lossy<S: Serializer>(path: &&Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// opens the journal for appending, creating it if needed
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Journal { file })
+    }
+
+    /// writes the entry as a line of JSON and makes sure it's hit the disk before returning
+    pub fn write(&mut self, entry: &JournalEntry) -> std::io::Result<()> {
+        serde_json::to_writer(&mut self.file, entry)?;
+        self.file.write_all(b"\n")?;
+        self.file.flush()?;
+        self.file.sync_data()
+    }
+}
+
+/// seconds since the unix epoch, for journal timestamps
+pub fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_are_written() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempfile::tempdir().unwrap();
+        let journal_file = dir.path().join("journal.jsonl");
+        let source = Path::new("/photos").join(OsStr::from_bytes(b"\xff.jpeg"));
+        let mut journal = Journal::open(&journal_file).unwrap();
+        journal
+            .write(&JournalEntry::Done {
+                source: &source,
+                dest: Path::new("/photos/a.jpg"),
+            })
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&journal_file).unwrap(),
+            "{\"action\":\"done\",\"source\":\"/photos/\u{fffd}.jpeg\",\"dest\":\"/photos/a.jpg\"}\n"
+        );
+    }
+}
@@ -0,0 +1,27 @@
+//! Finds files with a regex and works out what to rename them to, see [get_matched_paths] and [get_change_pairs].
+
+/// prints progress and status text, to stderr when stdout is carrying machine-readable output
+#[macro_export]
+macro_rules! status {
+    ($config:expr, $($arg:tt)*) => {
+        match $config.status_to_stderr {
+            true => eprintln!($($arg)*),
+            false => println!($($arg)*),
+        }
+    };
+}
+
+mod audio_tags;
+pub mod change_pairs;
+pub mod config;
+pub mod ext_map;
+mod fuzzy;
+#[cfg(feature = "lua")]
+mod lua;
+pub mod matching;
+mod pdf_metadata;
+mod template;
+
+pub use change_pairs::{get_change_pairs, ChangeSet};
+pub use config::Config;
+pub use matching::get_matched_paths;
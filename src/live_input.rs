@@ -0,0 +1,81 @@
+//! A single-line text input which redraws a preview above the prompt after every keypress.
+
+use std::io;
+
+use dialoguer::console::{style, truncate_str, Key, Term};
+
+/// how many preview lines to show above the prompt
+pub const LIVE_PREVIEW_LINES: usize = 5;
+
+/// like `dialoguer::Input`, but calls `preview` with the current text after every keypress
+/// and draws the lines it returns above the prompt
+pub struct LiveInput<F: Fn(&str) -> Vec<String>> {
+    prompt: String,
+    text: String,
+    preview: F,
+}
+
+impl<F: Fn(&str) -> Vec<String>> LiveInput<F> {
+    pub fn new(prompt: &str, preview: F) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            text: String::new(),
+            preview,
+        }
+    }
+
+    pub fn with_initial_text(mut self, text: &str) -> Self {
+        self.text = text.to_string();
+        self
+    }
+
+    /// draws the preview and prompt, returning how many preview lines were written
+    fn render(&self, term: &Term, previous_lines: usize) -> io::Result<usize> {
+        term.clear_line()?;
+        term.clear_last_lines(previous_lines)?;
+        let width = term.size().1 as usize;
+        let lines = (self.preview)(&self.text);
+        for line in lines.iter().take(LIVE_PREVIEW_LINES) {
+            term.write_line(&truncate_str(line, width.saturating_sub(1), "…"))?;
+        }
+        term.write_str(&format!(
+            "{} {} {}",
+            style("?").yellow(),
+            self.prompt,
+            self.text
+        ))?;
+        term.flush()?;
+        Ok(lines.len().min(LIVE_PREVIEW_LINES))
+    }
+
+    /// reads keys until Enter, Escape gives back the initial text
+    pub fn interact_on(self, term: &Term) -> io::Result<String> {
+        let initial = self.text.clone();
+        let mut input = self;
+        let mut drawn = input.render(term, 0)?;
+        loop {
+            match term.read_key()? {
+                Key::Enter => break,
+                Key::Escape => {
+                    input.text = initial;
+                    break;
+                }
+                Key::Backspace => {
+                    input.text.pop();
+                }
+                Key::Char(c) if !c.is_control() => input.text.push(c),
+                _ => continue,
+            }
+            drawn = input.render(term, drawn)?;
+        }
+        term.clear_line()?;
+        term.clear_last_lines(drawn)?;
+        term.write_line(&format!(
+            "{} {} {}",
+            style("✔").green(),
+            input.prompt,
+            input.text
+        ))?;
+        Ok(input.text)
+    }
+}
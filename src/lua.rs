@@ -0,0 +1,16 @@
+//! Renaming with user-supplied Lua scripts, behind the `lua` feature.
+//!
+//! The script gets the full path of the file as the `path` global, and returns the new filename, or `nil` to leave it alone.
+
+use std::path::{Path, PathBuf};
+
+use mlua::{Error as LuaError, Lua};
+
+/// runs the script for a single path, returning the new path if the script wants it renamed
+pub fn apply_lua_rename(script: &str, path: &Path) -> Result<Option<PathBuf>, LuaError> {
+    let lua = Lua::new();
+    lua.globals()
+        .set("path", path.to_string_lossy().to_string())?;
+    let result: Option<String> = lua.load(script).eval()?;
+    Ok(result.map(|filename| path.with_file_name(filename)))
+}
@@ -1,20 +1,65 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use std::process;
 use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 
-use clap::{arg, command, Parser};
+use clap::{Parser, Subcommand, ValueEnum};
 use dialoguer::console::Term;
-use dialoguer::theme::ColorfulTheme;
-use dialoguer::{Input, Select};
-use glob::{glob, Paths};
+use dialoguer::{Confirm, Input, Select};
+use live_input::{LiveInput, LIVE_PREVIEW_LINES};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use regex::Regex;
+#[cfg(feature = "lua")]
+use renamer::change_pairs::get_change_pairs_lua;
+use renamer::change_pairs::{
+    destination_taken, get_change_pairs, render_rename_template, replace_matches,
+    template_metadata, EXTENSION_SUGGESTIONS,
+};
+use renamer::config::{
+    format_conflict_suffix, ColorScheme, ConflictStrategy, CounterFormat, DateSource, MatchType,
+    NormForm, NumberFormat, PreviewColumn, PreviewFormat, RenamePart, SpaceMode,
+};
+use renamer::ext_map;
+use renamer::matching::{get_files, get_matched_paths, VERBOSE_FILE_LIMIT};
+use renamer::{status, ChangeSet, Config};
+
+mod check_regex;
+mod color_scheme;
+mod cycles;
+mod diff;
+mod dry_run_diff;
+mod fd;
+mod history;
+mod incremental;
+mod journal;
+mod live_input;
+mod output_csv;
+mod powershell;
+mod preview;
+mod profile;
+mod rename_csv;
+mod rename_json;
+mod rename_log;
+mod simulate;
+mod test_file;
+#[cfg(feature = "tui")]
+mod tui;
+mod validate;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[command(args_override_self = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// File path to read
-    #[arg()]
-    filepath: String,
+    #[arg(required_unless_present_any = ["stdin_paths", "test_file", "rename_csv", "from_json"])]
+    filepath: Option<String>,
     /// File regex to match
     #[arg(short, long)]
     regex_matcher: Option<String>,
@@ -25,72 +70,595 @@ struct Args {
     /// String to substitute in the filename
     #[arg(short, long)]
     substitute: Option<String>,
+
+    /// Print each file considered during matching, and whether it matched
+    #[arg(short, long)]
+    verbose: bool,
+    /// Print verbose output even when more than VERBOSE_FILE_LIMIT files are considered
+    #[arg(long)]
+    verbose_all: bool,
+
+    /// Seed the file matcher from fd-style arguments, eg '--type f --extension jpeg'
+    #[arg(long, allow_hyphen_values = true)]
+    from_fd_args: Option<String>,
+
+    /// Check the rename plan against an in-memory copy of the filesystem instead of renaming anything
+    #[arg(long)]
+    simulate: bool,
+
+    /// Rename even when the destination already exists, replacing it, same as --rename-conflicts overwrite
+    #[arg(long, conflicts_with = "rename_conflicts")]
+    overwrite: bool,
+    /// What to do when the destination already exists
+    #[arg(long, value_enum)]
+    rename_conflicts: Option<ConflictStrategy>,
+    /// What --rename-conflicts suffix puts between the stem and extension, {n} is the number and {n:02} zero-pads it
+    #[arg(long, default_value = "_{n}")]
+    conflict_suffix_format: String,
+    /// Overwrite a destination that already exists only if the source was modified more recently
+    #[arg(long, conflicts_with_all = ["rename_conflicts", "overwrite"])]
+    rename_if_newer_than_dest: bool,
+    /// Which order to apply the changes in, defaults to deep-first when renaming directories and the order they were found in otherwise
+    #[arg(long, value_enum)]
+    sort_apply: Option<DepthOrder>,
+    /// Rename directories before files, after any --sort-apply
+    #[arg(long, conflicts_with = "rename_files_first")]
+    rename_dirs_first: bool,
+    /// Rename files before directories, after any --sort-apply
+    #[arg(long)]
+    rename_files_first: bool,
+    /// When overwriting, move the existing destination to <destination><suffix> first
+    #[arg(long)]
+    backup_suffix: Option<String>,
+    /// Copy each file into this directory before renaming it, the rename's skipped if the copy fails
+    #[arg(long)]
+    backup_dir: Option<PathBuf>,
+    /// Keep the directory structure of the files under --backup-dir, instead of putting them all in one place
+    #[arg(long, requires = "backup_dir")]
+    backup_preserve_structure: bool,
+    /// Give the copies in --backup-dir the same access and modified times as the originals
+    #[arg(long, requires = "backup_dir")]
+    preserve_timestamps: bool,
+
+    /// Unicode normalization form to apply to destination filenames
+    #[arg(long, value_enum)]
+    normalize: Option<NormForm>,
+    /// What to do with spaces in destination filenames
+    #[arg(long, value_enum)]
+    encode_spaces: Option<SpaceMode>,
+
+    /// Don't send long previews through $PAGER
+    #[arg(long)]
+    no_pager: bool,
+    /// Scroll through long previews with the arrow keys instead of using $PAGER
+    #[arg(long)]
+    scroll_preview: bool,
+
+    /// Test the matcher and renamer against a single path, then exit without renaming anything
+    #[arg(long, conflicts_with = "stdin_paths")]
+    test_file: Option<PathBuf>,
+
+    /// Rename using the "source" and "destination" columns of a CSV file instead of regexes
+    #[arg(long, conflicts_with_all = ["stdin_paths", "test_file"])]
+    rename_csv: Option<PathBuf>,
+    /// Apply the renames from a plan written with --output-json, without scanning for files
+    #[arg(long, conflicts_with_all = ["stdin_paths", "test_file", "rename_csv"])]
+    from_json: Option<PathBuf>,
+    /// Apply the changes without asking after the preview, then exit
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Use the flags from this [profile.<name>] section of the config file, flags given here override them
+    #[arg(long)]
+    profile: Option<String>,
+    /// Config file to read --profile from, defaults to ~/.config/renamer/config.toml
+    #[arg(long, requires = "profile")]
+    config: Option<PathBuf>,
+
+    /// Read the paths to rename from stdin, one per line, instead of scanning a directory
+    #[arg(long)]
+    stdin_paths: bool,
+
+    /// Only change the extension of files from --find to --replace, skipping the regexes
+    #[arg(long, requires_all = ["find", "replace"], conflicts_with = "pattern")]
+    rename_extension_only: bool,
+    /// Extension to find when using --rename-extension-only
+    #[arg(long)]
+    find: Option<String>,
+    /// Extension to replace it with when using --rename-extension-only
+    #[arg(long)]
+    replace: Option<String>,
+
+    /// Longest destination filename allowed, in bytes, longer names have their stem truncated
+    #[arg(long, default_value_t = 255)]
+    max_filename_length: usize,
+    /// Add a … to filenames which have been truncated
+    #[arg(long)]
+    truncate_marker: bool,
+
+    /// Stop applying changes as soon as a rename fails
+    #[arg(long)]
+    fail_fast: bool,
+    /// Stop applying changes once this many renames have failed
+    #[arg(long, conflicts_with = "fail_fast")]
+    max_errors: Option<usize>,
+
+    /// Match against a single path component, 0 is the filename, 1 the parent directory and so on
+    #[arg(long)]
+    match_depth: Option<usize>,
+
+    /// Rename extensions without writing regexes, eg 'jpeg:jpg,JPEG:jpg'
+    #[arg(long, conflicts_with_all = ["pattern", "regex_matcher", "rename_extension_only"])]
+    ext_map: Option<String>,
+
+    /// Lua script which gets the file's `path` and returns its new filename, or nil to skip it
+    #[cfg(feature = "lua")]
+    #[arg(long, conflicts_with_all = ["pattern", "rename_extension_only", "ext_map"])]
+    script_file: Option<PathBuf>,
+
+    /// Append _ to destination names which are reserved on Windows (CON, NUL, COM1...) instead of skipping them
+    #[arg(long)]
+    sanitize: bool,
+
+    /// Write a JSON line to this file before and after each rename
+    #[arg(long)]
+    journal: Option<PathBuf>,
+    /// Print every path the file-matching regex matches, sorted, and exit without renaming anything
+    #[arg(long, conflicts_with_all = ["stdin_paths", "test_file", "rename_csv", "from_json"])]
+    list_matched: bool,
+    /// Write the renames to this file as a PowerShell script instead of applying them, - for stdout
+    #[arg(long)]
+    output_script_powershell: Option<String>,
+    /// Write the renames to this file as JSON instead of applying them, - for stdout, apply it later with --from-json
+    #[arg(long, conflicts_with_all = ["rename_csv", "from_json"])]
+    output_json: Option<String>,
+    /// Print the renames as a unified diff of each directory's listing and exit without renaming anything
+    #[arg(long, conflicts_with = "output_script_powershell")]
+    dry_run_diff: bool,
+    /// Write the changes to this CSV file with a status for each, which is filled in after applying
+    #[arg(long)]
+    output_csv: Option<PathBuf>,
+    /// Write the paths which don't need renaming to this file, one per line, - for stdout
+    #[arg(long)]
+    report_unchanged: Option<String>,
+    /// List the renames whose destination already exists and stop, exiting with 3 if there are any
+    #[arg(long)]
+    report_would_overwrite: bool,
+    /// Separate the paths in --report-unchanged with NUL instead of newlines
+    #[arg(long, requires = "report_unchanged")]
+    print0: bool,
+    /// Add a tab-separated line to this file for each change, saying whether it was renamed, skipped or failed
+    #[arg(long)]
+    output_rename_log: Option<PathBuf>,
+    /// Add a tab-separated line to this file for each change skipped because the destination exists
+    #[arg(long)]
+    rename_conflict_log: Option<PathBuf>,
+
+    /// Give matched files the extension of the type detected from their contents
+    #[arg(long, conflicts_with_all = ["pattern", "substitute", "rename_extension_only", "ext_map"])]
+    rename_by_content_type: bool,
+    /// Add this to the start of each matched file's stem, after any renamer regex
+    #[arg(long)]
+    append_prefix: Option<String>,
+    /// Add this to the end of each matched file's stem, before the extension and after any renamer regex
+    #[arg(long)]
+    append_suffix: Option<String>,
+    /// Take this off the start of each matched file's stem, skipping files whose stem doesn't start with it
+    #[arg(long)]
+    strip_prefix: Option<String>,
+    /// Take this off the end of each matched file's stem, skipping files whose stem doesn't end with it
+    #[arg(long)]
+    strip_suffix: Option<String>,
+
+    /// Move matched files into one of this many hex-named subdirectories, picked by a hash of the filename
+    #[arg(long, conflicts_with_all = ["pattern", "substitute", "rename_extension_only", "ext_map", "rename_by_content_type", "rename_template"])]
+    rename_by_hash_bucket: Option<u32>,
+    /// Move matched files into YYYY/MM/DD subdirectories of the file path, from when they were last modified
+    #[arg(long, conflicts_with_all = ["pattern", "substitute", "rename_extension_only", "ext_map", "rename_by_content_type", "rename_template", "rename_by_hash_bucket"])]
+    rename_by_modified_date: bool,
+    /// Like --rename-by-modified-date, but from when the files were created, or modified if that's not known
+    #[arg(long, conflicts_with_all = ["pattern", "substitute", "rename_extension_only", "ext_map", "rename_by_content_type", "rename_template", "rename_by_hash_bucket", "rename_by_modified_date"])]
+    rename_by_created_date: bool,
+
+    /// Use a template like '{{stem}}_copy.{{ext}}' for the new filename instead of a regex replacement
+    #[arg(long, conflicts_with = "substitute")]
+    rename_template: Option<String>,
+    /// What {{n}} in a template starts counting from
+    #[arg(long, default_value_t = 1, requires = "rename_template")]
+    counter_start: usize,
+    /// How much {{n}} in a template goes up by each time
+    #[arg(long, default_value_t = 1, requires = "rename_template")]
+    counter_step: usize,
+    /// Zero-pad {{n}} in a template to this many digits
+    #[arg(long, default_value_t = 0, requires = "rename_template")]
+    counter_width: usize,
+    /// Format {{n}} in a template with %d, %x, %X, %o or %b, eg %04x for zero-padded hex
+    #[arg(long, requires = "rename_template", conflicts_with = "counter_width")]
+    format_number: Option<NumberFormat>,
+    /// Start {{n}} again in each directory, instead of counting across all of them
+    #[arg(long, requires = "rename_template")]
+    rename_by_sequence_in_dir: bool,
+    /// Make the title, artist, album, track_number and year tags of audio files available in the template
+    #[arg(long, requires = "rename_template")]
+    rename_by_audio_tags: bool,
+    /// Make the title, author, creation date and subject of PDFs available in the template, as pdf_title etc
+    #[arg(long, requires = "rename_template")]
+    rename_by_pdf_metadata: bool,
+
+    /// Skip files matching this glob, against either the full path or the filename, can be repeated
+    #[arg(long)]
+    exclude_glob: Vec<String>,
+
+    /// Treat the file-matching regex as literal text, so photo.jpeg doesn't match photoXjpeg
+    #[arg(long)]
+    escape_special: bool,
+
+    /// Put this before the file-matching and renamer regexes, eg '(?i)' to ignore case in both
+    #[arg(long, default_value = "")]
+    wrap_regex_prefix: String,
+    /// Put this after the file-matching and renamer regexes
+    #[arg(long, default_value = "")]
+    wrap_regex_suffix: String,
+
+    /// Run the renamer over the whole canonical path, so directories can be renamed too, eg photos -> pictures
+    #[arg(long, conflicts_with = "rename_template")]
+    match_whole_path: bool,
+    /// Only replace the Nth match of the renamer regex (counting from 1), instead of all of them
+    #[arg(long, conflicts_with = "rename_template")]
+    regex_replace_nth: Option<usize>,
+    /// Only run the renamer over this part of the path, the rest is kept as it is
+    #[arg(long, value_enum, conflicts_with_all = ["rename_template", "match_whole_path", "regex_replace_nth"])]
+    rename_part: Option<RenamePart>,
+    /// Create the destination's parent directories if they don't exist
+    #[arg(long)]
+    create_dirs: bool,
+
+    /// Match the letters in the file-matching regex in either case, so jpeg matches JPEG and Jpeg too
+    #[arg(long)]
+    match_case_variants: bool,
+
+    /// Allow renamer regexes with no capture groups, or more than one
+    #[arg(long)]
+    skip_regex_validation: bool,
+
+    /// Match paths loosely against these space-separated words instead of using the file-matching regex
+    #[arg(long, conflicts_with = "regex_matcher")]
+    fuzzy_match: Option<String>,
+    /// How closely a path has to match --fuzzy-match, from 0 to 1
+    #[arg(long, default_value_t = 0.5, requires = "fuzzy_match")]
+    fuzzy_threshold: f64,
+
+    /// Rename the files the file-matching regex doesn't match, like grep -v (-v is --verbose here)
+    #[arg(long)]
+    invert_match: bool,
+
+    /// Which kinds of entries to match, when renaming directories use --sort-apply deep-first too
+    #[arg(long, value_enum, default_value_t = MatchType::File)]
+    match_type: MatchType,
+
+    /// Ask for the file-matching regex even when a shorthand mode like --rename-extension-only has made one
+    #[arg(long)]
+    interactive_mode: bool,
+    /// Go through previously entered regexes with the up and down arrows, kept between sessions
+    #[arg(long)]
+    interactive_regex_history: bool,
+
+    /// Only match files modified since the last apply with the same arguments
+    #[arg(long, conflicts_with_all = ["stdin_paths", "rename_csv", "from_json"])]
+    rename_incremental: bool,
+
+    /// Give up if matching files takes longer than this many milliseconds
+    #[arg(long)]
+    regex_timeout: Option<u64>,
+    /// After matching, list how many of each extension weren't matched, eg to spot .JPG when matching .jpg
+    #[arg(long)]
+    summarize_unmatched: bool,
+
+    /// Only rename paths under this subdirectory of the file path
+    #[arg(long)]
+    only_in: Option<PathBuf>,
+
+    /// How to show the preview of changes
+    #[arg(long, value_enum, default_value_t = PreviewFormat::Table)]
+    preview_format: PreviewFormat,
+    /// Colours for the preview table headers and the prompts
+    #[arg(long, value_enum)]
+    color_scheme: Option<ColorScheme>,
+    /// Which columns to show in the preview table, comma separated
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "source,dest")]
+    preview_cols: Vec<PreviewColumn>,
+
+    /// Skip over errors while scanning for files, this is the default
+    #[arg(long, conflicts_with = "strict")]
+    ignore_errors: bool,
+    /// Stop if there's any errors while scanning for files
+    #[arg(long)]
+    strict: bool,
+
+    /// Use the full-screen interface to build the regexes, with a live preview
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with = "stdin_paths")]
+    tui: bool,
+
+    /// Print a one line summary of each apply to stderr
+    #[arg(long)]
+    stats_after: bool,
+
+    /// Show how long scanning and matching files, working out the renames, rendering the preview and applying changes took
+    #[arg(long)]
+    timing: bool,
+
+    /// Exit with code 2 if the matcher doesn't find any files
+    #[arg(long)]
+    fail_on_no_match: bool,
+
+    /// Exit with an error unless exactly this many files match
+    #[arg(long)]
+    assert_count: Option<usize>,
+    /// Exit with an error if fewer than this many files match
+    #[arg(long)]
+    assert_min_count: Option<usize>,
+    /// Exit with an error if more than this many files match
+    #[arg(long)]
+    assert_max_count: Option<usize>,
+
+    /// Only preview this many changes, picked at random, applying still renames everything that matched
+    #[arg(long)]
+    sample: Option<usize>,
+    /// Seed for --sample, so the same changes are picked each time
+    #[arg(long, requires = "sample")]
+    seed: Option<u64>,
+
+    /// Show this many changes at a time in the preview, 0 shows them all at once
+    #[arg(long, default_value_t = 50)]
+    interactive_preview_limit: usize,
+    /// List this many of the matched paths before asking for the renamer, 0 for none or all for every one
+    #[arg(long, default_value = "10")]
+    preview_n: PreviewCount,
 }
 
-fn get_files(args: &Args) -> Option<Paths> {
-    let pattern = match args.filepath.ends_with('/') {
-        true => format!("{}**/*", args.filepath),
-        false => format!("{}/**/*", args.filepath),
-    };
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Check a regex is valid and explain what it matches
+    CheckRegex {
+        /// The regex to check
+        pattern: String,
+        /// Also print the parsed regex as a tree
+        #[arg(long)]
+        tree: bool,
+    },
+}
 
-    match glob(&pattern) {
-        Ok(val) => Some(val),
-        Err(error) => {
-            eprintln!(
-                "Failed to parse directory pattern ({}): {error:?}",
-                &pattern
-            );
-            None
+/// which order to apply changes in, see [sort_by_depth]
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum DepthOrder {
+    /// rename the most deeply nested paths first, so renaming a directory doesn't move paths still to be renamed
+    DeepFirst,
+    /// rename the least nested paths first
+    ShallowFirst,
+    /// keep the order they were found in
+    #[default]
+    None,
+}
+
+/// sorts the changes by how many components the source path has, keeping the order within each depth
+fn sort_by_depth(changes: &mut [(PathBuf, PathBuf)], mode: DepthOrder) {
+    match mode {
+        DepthOrder::DeepFirst => {
+            changes.sort_by_key(|(source, _)| std::cmp::Reverse(source.components().count()))
         }
+        DepthOrder::ShallowFirst => changes.sort_by_key(|(source, _)| source.components().count()),
+        DepthOrder::None => {}
     }
 }
 
-fn get_matched_paths(args: &Args, matcher_regex: Regex) -> Vec<PathBuf> {
-    println!("Finding files...");
-    get_files(args)
-        .unwrap()
-        .filter_map(|p| {
-            match p {
-                Ok(path) => {
-                    let path_string = path.as_os_str();
-                    let path_string = path_string.to_str().unwrap();
-                    match matcher_regex.is_match(path_string) {
-                        true => {
-                            // let path: String = path_string.into();
-                            Some(path)
-                        }
-                        false => {
-                            // eprintln!("Failed to match {path_string}");
-                            None
-                        }
-                    }
-                }
-                Err(err) => {
-                    eprintln!("Error: {err:?}");
-                    None
+type Changes = Vec<(PathBuf, PathBuf)>;
+
+/// puts the changes in the order they're applied in, --sort-apply defaults to deep-first when directories are renamed
+fn order_for_apply(mut changes: Changes, args: &Args) -> Changes {
+    let renames_dirs = changes
+        .iter()
+        .any(|(source, dest)| source != dest && source.is_dir());
+    let depth_order = args.sort_apply.unwrap_or(match renames_dirs {
+        true => DepthOrder::DeepFirst,
+        false => DepthOrder::None,
+    });
+    sort_by_depth(&mut changes, depth_order);
+    if args.rename_dirs_first || args.rename_files_first {
+        changes = order_by_type(changes, args.rename_dirs_first);
+    }
+    match depth_order == DepthOrder::DeepFirst && !args.rename_dirs_first {
+        true => rename_under_current_parents(changes),
+        false => changes,
+    }
+}
+
+/// when a directory's renamed after what's inside it, those have to be renamed under its current name
+///
+/// eg `a_x/b_x -> a_y/b_y` becomes `a_x/b_x -> a_x/b_y`, and `a_x -> a_y` then moves it to where it's meant to be
+fn rename_under_current_parents(changes: Changes) -> Changes {
+    let renamed_dirs: HashMap<PathBuf, PathBuf> = changes
+        .iter()
+        .filter(|(source, dest)| source != dest && source.is_dir())
+        .cloned()
+        .collect();
+    changes
+        .into_iter()
+        .map(
+            |(source, dest)| match (source.parent(), dest.parent(), dest.file_name()) {
+                (Some(source_parent), Some(dest_parent), Some(filename))
+                    if renamed_dirs.get(source_parent).map(PathBuf::as_path)
+                        == Some(dest_parent) =>
+                {
+                    let dest = source_parent.join(filename);
+                    (source, dest)
                 }
-            }
-        })
+                _ => (source, dest),
+            },
+        )
         .collect()
 }
 
-// builds the regex and tries to clean it up
-fn get_matcher_regex(matcher_string: &str) -> Result<Regex, regex::Error> {
-    let mut matcher_string_temp = matcher_string.to_string();
+/// splits the changes into (directories, everything else), keeping their order
+fn partition_changes(changes: Changes) -> (Changes, Changes) {
+    changes.into_iter().partition(|(source, _)| source.is_dir())
+}
+
+/// puts the directories before the files, or after them, keeping the order within each
+fn order_by_type(changes: Changes, dirs_first: bool) -> Changes {
+    let (dirs, files) = partition_changes(changes);
+    match dirs_first {
+        true => dirs.into_iter().chain(files).collect(),
+        false => files.into_iter().chain(dirs).collect(),
+    }
+}
+
+/// works out where a conflicting change should go, only [ConflictStrategy::Suffix] changes the destination
+fn resolve_conflict(dest: &Path, strategy: ConflictStrategy, suffix_format: &str) -> PathBuf {
+    match strategy {
+        ConflictStrategy::Suffix => {
+            let stem = dest.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = match dest.extension() {
+                Some(ext) => format!(".{}", ext.to_string_lossy()),
+                None => String::new(),
+            };
+            (1..)
+                .map(|n| {
+                    let suffix = format_conflict_suffix(suffix_format, n);
+                    dest.with_file_name(format!("{stem}{suffix}{extension}"))
+                })
+                .find(|candidate| !candidate.exists())
+                .unwrap_or(dest.to_path_buf())
+        }
+        ConflictStrategy::Skip | ConflictStrategy::Overwrite | ConflictStrategy::Error => {
+            dest.to_path_buf()
+        }
+    }
+}
+
+/// a number of paths to show, or all of them
+#[derive(Clone, Copy, Debug)]
+struct PreviewCount(Option<usize>);
+
+impl FromStr for PreviewCount {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "all" => Ok(PreviewCount(None)),
+            value => value
+                .parse()
+                .map(|count| PreviewCount(Some(count)))
+                .map_err(|_| format!("expected a number or all, got {value}")),
+        }
+    }
+}
+
+/// runs `work`, giving up and exiting if it takes longer than --regex-timeout
+fn with_regex_timeout<T: Send>(args: &Args, work: impl FnOnce() -> T + Send) -> T {
+    let Some(timeout) = args.regex_timeout else {
+        return work();
+    };
+    std::thread::scope(|scope| {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        scope.spawn(move || sender.send(work()));
+        match receiver.recv_timeout(Duration::from_millis(timeout)) {
+            Ok(result) => result,
+            Err(_) => {
+                // the scan can't be stopped part way through, so leave it running and exit
+                eprintln!("Warning: matching files took longer than {timeout}ms, giving up");
+                process::exit(1);
+            }
+        }
+    })
+}
+
+/// runs [get_matched_paths] with --regex-timeout, collected for the prompts that show them
+fn get_matched_paths_with_timeout(
+    args: &Args,
+    config: &Config,
+    matcher_regex: Regex,
+) -> Vec<PathBuf> {
+    let file_path = args.filepath.as_deref().unwrap_or(".");
+    with_regex_timeout(args, || {
+        get_matched_paths(file_path, config, matcher_regex).collect()
+    })
+}
+
+/// the matched paths as far as the listing and the --assert-*count checks need them, so the rest can stream past
+struct MatchListing {
+    count: usize,
+    /// the first --preview-n paths, or all of them when a failed count check would list them
+    paths: Vec<PathBuf>,
+    limit: Option<usize>,
+}
 
-    if !matcher_string_temp.ends_with('$') {
-        matcher_string_temp = format!("{matcher_string_temp}$");
+impl MatchListing {
+    fn new(args: &Args, config: &Config) -> Self {
+        let checks_count = args.assert_count.is_some()
+            || args.assert_min_count.is_some()
+            || args.assert_max_count.is_some();
+        MatchListing {
+            count: 0,
+            paths: Vec::new(),
+            limit: match checks_count {
+                true => None,
+                false => config.preview_count,
+            },
+        }
+    }
+
+    fn add(&mut self, path: &Path) {
+        self.count += 1;
+        if self.limit.is_none_or(|limit| self.paths.len() < limit) {
+            self.paths.push(path.to_path_buf());
+        }
     }
-    println!("Creating regex on {matcher_string_temp}");
+}
+
+/// pairs the paths up with the Lua script if there is one, otherwise the renamer
+fn change_pairs(
+    paths: impl IntoIterator<Item = PathBuf>,
+    base_path: &str,
+    renamer_regex: Option<Regex>,
+    config: &Config,
+) -> ChangeSet {
+    #[cfg(feature = "lua")]
+    if let Some(ref script) = config.lua_script {
+        return get_change_pairs_lua(paths, script, config);
+    }
+    get_change_pairs(paths, base_path.to_string(), renamer_regex, config)
+}
+
+/// true when the only regex syntax in the matcher is unescaped dots, like someone's pasted in photo.jpeg
+fn looks_like_filename(matcher_string: &str) -> bool {
+    matcher_string.contains('.')
+        && regex::escape(matcher_string) == matcher_string.replace('.', r"\.")
+}
+
+// builds the regex and tries to clean it up
+fn get_matcher_regex(config: &Config) -> Result<Regex, regex::Error> {
+    let matcher_string_temp = config.matcher_pattern();
+    status!(config, "Creating regex on {matcher_string_temp}");
     Regex::new(&matcher_string_temp)
 }
 
 // builds the regex and tries to clean it up
-fn get_renamer_regex(renamer_string: &str) -> Result<Regex, String> {
-    let renamer_string_temp = renamer_string.to_string();
+fn get_renamer_regex(config: &Config) -> Result<Regex, String> {
+    status!(
+        config,
+        "Creating renamer regex on {}",
+        config.renamer_pattern()
+    );
+    build_renamer_regex(config)
+}
 
-    println!("Creating renamer regex on {renamer_string_temp}");
-    let regex = Regex::new(&renamer_string_temp).map_err(|err| format!("{err:?}"))?;
+/// [get_renamer_regex] without the status line, checking it's got the one capture group
+fn build_renamer_regex(config: &Config) -> Result<Regex, String> {
+    let regex = Regex::new(&config.renamer_pattern()).map_err(|err| format!("{err:?}"))?;
+    if config.skip_regex_validation {
+        return Ok(regex);
+    }
     if regex.capture_names().len() == 1 {
         return Err("You don't have any capture groups for renaming?".to_string());
     }
@@ -102,199 +670,1571 @@ fn get_renamer_regex(renamer_string: &str) -> Result<Regex, String> {
     Ok(regex)
 }
 
-struct Config {
-    pub matcher_string: String,
-    pub renamer_string: String,
-    pub replacement_string: String,
-    pub show_unchanged: bool,
+/// pulls the literal characters off the end of a regex pattern, eg `jpeg` from `.*\.jpeg$`
+fn literal_suffix(pattern: &str) -> String {
+    let pattern = pattern.strip_suffix('$').unwrap_or(pattern);
+    let mut literal: Vec<char> = pattern
+        .chars()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+        .collect();
+    literal.reverse();
+
+    // an escaped letter like \d is a class, not a literal
+    let prefix = &pattern[..pattern.len() - literal.iter().map(|c| c.len_utf8()).sum::<usize>()];
+    if prefix.ends_with('\\') && !literal.is_empty() {
+        literal.remove(0);
+    }
+    literal.into_iter().collect()
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            matcher_string: r".*\.jpeg$".to_string(),
-            renamer_string: "(jpeg)".to_string(),
-            replacement_string: "jpg".to_string(),
-            show_unchanged: true,
+/// looks at the literal end of the matcher and the matched paths, and suggests a renamer regex and replacement
+fn suggest_renamer(matcher_regex: &Regex, paths: &[PathBuf]) -> Option<(String, String)> {
+    let literal = literal_suffix(matcher_regex.as_str());
+    if literal.is_empty()
+        || !paths
+            .iter()
+            .all(|path| path.to_string_lossy().ends_with(&literal))
+    {
+        return None;
+    }
+
+    let replacement = EXTENSION_SUGGESTIONS
+        .iter()
+        .find(|(from, _)| from.eq_ignore_ascii_case(&literal))
+        .map(|(_, to)| to.to_string())
+        .unwrap_or(literal.to_lowercase());
+    Some((format!("({})$", regex::escape(&literal)), replacement))
+}
+
+/// describes in words what the renamer regex and replacement did to turn `src` into `dest`
+fn explain_change(
+    src: &Path,
+    dest: &Path,
+    renamer: &Regex,
+    replacement: &str,
+    nth: Option<usize>,
+) -> String {
+    let src_name = src.file_name().unwrap_or_default().to_string_lossy();
+    let dest_name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let mut lines = Vec::new();
+    for (index, captures) in renamer.captures_iter(&src_name).enumerate() {
+        if nth.is_some_and(|nth| nth != index + 1) {
+            continue;
         }
+        let Some(whole) = captures.get(0) else {
+            continue;
+        };
+        lines.push(format!(
+            "The renamer regex matched '{}' in '{src_name}'.",
+            whole.as_str()
+        ));
+        let names: Vec<Option<&str>> = renamer.capture_names().collect();
+        captures
+            .iter()
+            .enumerate()
+            .skip(1)
+            .for_each(|(index, group)| {
+                let label = match names.get(index).copied().flatten() {
+                    Some(name) => format!("Capture group {index} ({name})"),
+                    None => format!("Capture group {index}"),
+                };
+                lines.push(match group {
+                    Some(group) => format!("{label} was '{}'.", group.as_str()),
+                    None => format!("{label} didn't take part in the match."),
+                });
+            });
+        let mut expanded = String::new();
+        captures.expand(replacement, &mut expanded);
+        let target = match captures.len() {
+            2 if captures.get(1).map(|group| group.range()) == Some(whole.range()) => {
+                "capture group 1".to_string()
+            }
+            _ => format!("'{}'", whole.as_str()),
+        };
+        lines.push(match expanded == replacement {
+            true => format!("The replacement string '{replacement}' was substituted for {target}."),
+            false => format!(
+                "The replacement string '{replacement}' became '{expanded}' and was substituted for {target}."
+            ),
+        });
+    }
+    if lines.is_empty() {
+        lines.push(format!(
+            "The renamer regex didn't match '{src_name}', so the filename was left as it was."
+        ));
+    }
+    let replaced = replace_matches(renamer, &src_name, replacement, nth);
+    match replaced == dest_name {
+        true => lines.push(format!("That yields '{dest_name}'.")),
+        false => lines.push(format!(
+            "That yields '{replaced}', which was cleaned up to '{dest_name}'."
+        )),
+    }
+    if src.parent() != dest.parent() {
+        lines.push(format!(
+            "It moves from {} to {}.",
+            src.parent().unwrap_or(Path::new("")).to_string_lossy(),
+            dest.parent().unwrap_or(Path::new("")).to_string_lossy()
+        ));
     }
+    lines.join("\n")
 }
 
-/// takes the found paths, the base path, matcher regex and replacement string and returns a list of start -> end
-fn get_change_pairs(
-    paths: Vec<PathBuf>,
-    base_path: String,
-    matcher_regex: Regex,
-    replacement_string: &String,
-) -> Vec<(PathBuf, PathBuf)> {
+/// shows what the renamer regex captures on the first path it matches, and what the replacement would turn it into
+fn show_capture_preview(regex: &Regex, paths: &[PathBuf], replacement: &str) -> String {
+    let Some((filename, captures)) = paths.iter().find_map(|path| {
+        let filename = path.file_name()?.to_str()?;
+        regex
+            .captures(filename)
+            .map(|captures| (filename, captures))
+    }) else {
+        return "The renamer regex doesn't match any of the matched filenames!".to_string();
+    };
+
+    let mut preview: Vec<String> = captures
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(index, group)| match group {
+            Some(group) => format!("Capture group {index}: '{}'", group.as_str()),
+            None => format!("Capture group {index}: (no match)"),
+        })
+        .collect();
+    preview.push(format!("Full match: '{filename}'"));
+    preview.push(format!(
+        "Result: '{}'",
+        regex.replace_all(filename, replacement)
+    ));
+    preview.join(", ")
+}
+
+/// the first few source -> destination filenames, shown while the replacement is being typed
+fn live_preview(
+    paths: &[PathBuf],
+    renamer_regex: &Regex,
+    replacement: &str,
+    config: &Config,
+) -> Vec<String> {
     paths
-        .into_iter()
-        .map(|path| {
-            let path_str = path.to_str().unwrap();
-            let path_str: String = path_str.replace(&base_path, "");
-            let result = matcher_regex
-                .replace_all(&path_str, replacement_string)
-                .to_string();
-
-            let dest = PathBuf::from_str(&format!("{}{}", base_path, result)).unwrap();
-            (path.clone(), dest)
+        .iter()
+        .filter_map(|path| Some((path, path.file_name()?.to_str()?)))
+        .take(LIVE_PREVIEW_LINES)
+        .enumerate()
+        .map(|(index, (path, filename))| {
+            let result = match config.rename_template {
+                true => config
+                    .counter
+                    .format(index)
+                    .and_then(|counter| {
+                        render_rename_template(
+                            path,
+                            filename,
+                            renamer_regex,
+                            replacement,
+                            &counter,
+                            template_metadata(path, config).unwrap_or_default(),
+                        )
+                    })
+                    .map(|dest| {
+                        dest.file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .to_string()
+                    })
+                    .unwrap_or_else(|err| format!("({err})")),
+                false => replace_matches(renamer_regex, filename, replacement, config.replace_nth),
+            };
+            format!("  {filename} → {result}")
         })
         .collect()
 }
 
-fn apply_changes(changes: Vec<(PathBuf, PathBuf)>) {
-    let mut files_changed = 0;
-    let mut files_skipped = 0;
+/// `$1`, `${name}` and the like in a replacement string, and `$$` so an escaped dollar sign isn't mistaken for one
+static GROUP_REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\$|\$\{[^}]*\}|\$[0-9A-Za-z_]+").unwrap());
+
+/// looks for things in the replacement string that look like regex syntax, which might not do what the user expects
+fn replacement_warnings(replacement: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    GROUP_REFERENCE
+        .find_iter(replacement)
+        .filter(|found| found.as_str() != "$$")
+        .for_each(|found| {
+            let name = found.as_str().trim_start_matches('$');
+            let name = name.trim_start_matches('{').trim_end_matches('}');
+            warnings.push(format!(
+                "Your replacement string contains '{}' — this refers to capture group {name}. Did you mean a literal dollar sign? Use '${}'.",
+                found.as_str(),
+                found.as_str()
+            ));
+        });
+
+    let literal_metachars: Vec<char> = ['\\', '(', ')', '[', ']', '*', '+', '?', '^', '|']
+        .into_iter()
+        .filter(|c| replacement.contains(*c))
+        .collect();
+    if !literal_metachars.is_empty() {
+        warnings.push(format!(
+            "Your replacement string contains {} — these aren't regex syntax in a replacement, they'll be used literally.",
+            literal_metachars
+                .iter()
+                .map(|c| format!("'{c}'"))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+    warnings
+}
+
+/// writes each path as-is followed by a newline, or a NUL when `print0` is set
+fn write_path_list(
+    paths: &[&PathBuf],
+    print0: bool,
+    writer: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    let separator: &[u8] = match print0 {
+        true => b"\0",
+        false => b"\n",
+    };
+    for path in paths {
+        writer.write_all(path.as_os_str().as_encoded_bytes())?;
+        writer.write_all(separator)?;
+    }
+    writer.flush()
+}
+
+/// true if `source` was modified after `dest`, or either's modified time can't be read
+fn is_newer(source: &Path, dest: &Path) -> bool {
+    let modified = |path: &Path| path.metadata().and_then(|metadata| metadata.modified());
+    match (modified(source), modified(dest)) {
+        (Ok(source), Ok(dest)) => source > dest,
+        _ => true,
+    }
+}
+
+/// picks `amount` of the changes at random to preview, keeping them in order, anything held back is kept as is
+fn sample_changeset(changeset: &ChangeSet, amount: usize, seed: Option<u64>) -> ChangeSet {
+    let length = changeset.changes.len();
+    let amount = amount.min(length);
+    let mut indices = match seed {
+        Some(seed) => rand::seq::index::sample(&mut StdRng::seed_from_u64(seed), length, amount),
+        None => rand::seq::index::sample(&mut rand::rng(), length, amount),
+    }
+    .into_vec();
+    indices.sort_unstable();
+    ChangeSet {
+        changes: indices
+            .into_iter()
+            .map(|index| changeset.changes[index].clone())
+            .collect(),
+        blocked: changeset.blocked.clone(),
+        truncated: changeset.truncated.clone(),
+        reserved_names: changeset.reserved_names.clone(),
+        skipped_no_metadata: changeset.skipped_no_metadata.clone(),
+    }
+}
+
+/// what happened when changes were applied
+#[derive(Debug, Default)]
+struct ApplySummary {
+    /// source -> destination pairs which were actually renamed
+    pub applied: Vec<(PathBuf, PathBuf)>,
+    pub skipped: usize,
+    pub failed: usize,
+    /// what happened to each change, for --output-rename-log
+    pub log: Vec<rename_log::LogEntry>,
+    /// changes skipped because the destination exists, for --rename-conflict-log
+    pub conflicts: Vec<rename_log::LogEntry>,
+}
+
+impl ApplySummary {
+    fn renamed(&mut self, source: PathBuf, dest: PathBuf) {
+        self.log.push(rename_log::LogEntry::new(
+            &source,
+            &dest,
+            rename_log::LogStatus::Renamed,
+            None,
+        ));
+        self.applied.push((source, dest));
+    }
 
-    changes.iter().for_each(|(source_file, dest_file)| {
+    fn skip(&mut self, source: &Path, dest: &Path, reason: &str) {
+        self.skipped += 1;
+        self.log.push(rename_log::LogEntry::new(
+            source,
+            dest,
+            rename_log::LogStatus::Skipped,
+            Some(reason.to_string()),
+        ));
+    }
+
+    /// skips a change because the destination exists
+    fn conflict(&mut self, source: &Path, dest: &Path, reason: &str) {
+        self.skip(source, dest, reason);
+        self.conflicts.push(rename_log::LogEntry::new(
+            source,
+            dest,
+            rename_log::LogStatus::Skipped,
+            Some(reason.to_string()),
+        ));
+    }
+
+    fn fail(&mut self, source: &Path, dest: &Path, error: &std::io::Error) {
+        self.failed += 1;
+        self.log.push(rename_log::LogEntry::new(
+            source,
+            dest,
+            rename_log::LogStatus::Failed,
+            Some(error.to_string()),
+        ));
+    }
+
+    fn print(&self, config: &Config) {
+        status!(config, "Files changed: {}", self.applied.len());
+        status!(config, "Files skipped: {}", self.skipped);
+        if self.failed > 0 {
+            status!(config, "Files failed: {}", self.failed);
+        }
+    }
+
+    /// a single line to stderr for --stats-after, with the skips broken down by reason
+    fn print_summary(&self, elapsed: Duration) {
+        let mut skip_reasons: Vec<(&str, usize)> = Vec::new();
+        self.log
+            .iter()
+            .filter(|entry| entry.status == rename_log::LogStatus::Skipped)
+            .for_each(|entry| {
+                let reason = entry.error_message.as_deref().unwrap_or("unknown");
+                match skip_reasons.iter_mut().find(|(seen, _)| *seen == reason) {
+                    Some((_, count)) => *count += 1,
+                    None => skip_reasons.push((reason, 1)),
+                }
+            });
+        let skipped: Vec<String> = skip_reasons
+            .iter()
+            .map(|(reason, count)| format!("{count} skipped ({reason})"))
+            .collect();
+        let skipped = match skipped.is_empty() {
+            true => "0 skipped".to_string(),
+            false => skipped.join(", "),
+        };
+        eprintln!(
+            "Session complete: {} renamed, {skipped}, {} failed. Elapsed: {:.1}s.",
+            self.applied.len(),
+            self.failed,
+            elapsed.as_secs_f64()
+        );
+    }
+}
+
+/// how long each phase took, for --timing
+#[derive(Debug, Default)]
+struct Timings {
+    /// scanning the file path and running the matcher over what's found, and the renaming too when they're streamed together
+    pub scan_and_match: Duration,
+    /// working out the destination of each matched path
+    pub rename: Duration,
+    pub render: Duration,
+    pub apply: Duration,
+}
+
+impl Timings {
+    fn print(&self, config: &Config) {
+        let total = self.scan_and_match + self.rename + self.render + self.apply;
+        status!(
+            config,
+            "Scan and match: {:.2}s, Rename: {:.2}s, Render: {:.2}s, Apply: {:.2}s, Total: {:.2}s",
+            self.scan_and_match.as_secs_f64(),
+            self.rename.as_secs_f64(),
+            self.render.as_secs_f64(),
+            self.apply.as_secs_f64(),
+            total.as_secs_f64()
+        );
+    }
+}
+
+/// applying changes stopped part way through, `summary` has what was done before it stopped
+#[derive(Debug)]
+struct ApplyError {
+    pub summary: ApplySummary,
+    pub reason: String,
+}
+
+/// copies `source` into `backup_dir`, either by filename or under its full path when preserving the structure
+///
+/// refuses to replace an existing backup, eg two sources with the same filename in different directories,
+/// and copying gives the backup new timestamps unless `preserve_timestamps` is set
+fn backup_to_dir(
+    source: &Path,
+    backup_dir: &Path,
+    preserve_structure: bool,
+    preserve_timestamps: bool,
+) -> std::io::Result<PathBuf> {
+    let backup_file = match preserve_structure {
+        true => backup_dir.join(
+            source
+                .components()
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .collect::<PathBuf>(),
+        ),
+        false => backup_dir.join(source.file_name().unwrap_or(source.as_os_str())),
+    };
+    if backup_file.try_exists()? {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{backup_file:?} already exists, use --backup-preserve-structure to keep backups of files with the same name apart"),
+        ));
+    }
+    if let Some(parent) = backup_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let metadata = source.metadata()?;
+    std::fs::copy(source, &backup_file)?;
+    if preserve_timestamps {
+        filetime::set_file_times(
+            &backup_file,
+            filetime::FileTime::from_last_access_time(&metadata),
+            filetime::FileTime::from_last_modification_time(&metadata),
+        )?;
+    }
+    Ok(backup_file)
+}
+
+/// renames each destination back to its source, most recent first, for undoing an apply in the same session
+fn undo_changes(applied: Vec<(PathBuf, PathBuf)>) -> Vec<(PathBuf, PathBuf)> {
+    let mut restored = Vec::new();
+    for (source_file, dest_file) in applied.into_iter().rev() {
+        if source_file.exists() {
+            eprintln!("{source_file:?} exists again, not moving {dest_file:?} back");
+            continue;
+        }
+        println!("moving {dest_file:?} back to {source_file:?}");
+        match std::fs::rename(&dest_file, &source_file) {
+            Ok(()) => restored.push((dest_file, source_file)),
+            Err(err) => eprintln!("Failed to undo: {err:?}"),
+        }
+    }
+    println!("Files restored: {}", restored.len());
+    restored
+}
+
+/// swaps each path for where the renames moved it, so going round again with --stdin-paths finds them
+///
+/// the renames are followed in the order they were made, a directory's moves everything under it too
+fn follow_renames(paths: &mut [PathBuf], renamed: &[(PathBuf, PathBuf)]) {
+    let renames: HashMap<&Path, (usize, &Path)> = renamed
+        .iter()
+        .enumerate()
+        .map(|(index, (source, dest))| (source.as_path(), (index, dest.as_path())))
+        .collect();
+    for path in paths.iter_mut() {
+        let mut followed: Option<usize> = None;
+        loop {
+            // the first rename of the path, or a directory it's in, that hasn't been followed yet
+            let next = path
+                .ancestors()
+                .filter_map(|ancestor| {
+                    renames
+                        .get(ancestor)
+                        .map(|&(index, dest)| (index, ancestor, dest))
+                })
+                .filter(|(index, _, _)| followed.is_none_or(|followed| *index > followed))
+                .min_by_key(|(index, _, _)| *index)
+                .map(|(index, source, dest)| match path.strip_prefix(source) {
+                    Ok(rest) if !rest.as_os_str().is_empty() => (index, dest.join(rest)),
+                    _ => (index, dest.to_path_buf()),
+                });
+            let Some((index, moved)) = next else {
+                break;
+            };
+            *path = moved;
+            followed = Some(index);
+        }
+    }
+}
+
+/// applies the changes, then adds what happened to the --output-rename-log file
+fn apply_changes(
+    changes: Vec<(PathBuf, PathBuf)>,
+    config: &Config,
+) -> Result<ApplySummary, ApplyError> {
+    let result = apply_changes_unlogged(changes, config);
+    let summary = match result {
+        Ok(ref summary) => summary,
+        Err(ref err) => &err.summary,
+    };
+    if let Some(ref log_file) = config.output_rename_log {
+        if let Err(err) = rename_log::append_tsv_log(log_file, &summary.log) {
+            eprintln!("Failed to write rename log {log_file:?}: {err:?}");
+        }
+    }
+    if let Some(ref log_file) = config.rename_conflict_log {
+        if let Err(err) = rename_log::append_conflict_log(log_file, &summary.conflicts) {
+            eprintln!("Failed to write conflict log {log_file:?}: {err:?}");
+        }
+    }
+    result
+}
+
+fn apply_changes_unlogged(
+    changes: Vec<(PathBuf, PathBuf)>,
+    config: &Config,
+) -> Result<ApplySummary, ApplyError> {
+    let mut summary = ApplySummary::default();
+
+    if config.simulate {
+        simulate::simulate_changes(&changes).print();
+        return Ok(summary);
+    }
+
+    if let Some(ref backup_dir) = config.backup_dir {
+        if let Err(err) = std::fs::create_dir_all(backup_dir) {
+            return Err(ApplyError {
+                summary,
+                reason: format!("Failed to create backup directory {backup_dir:?}: {err}"),
+            });
+        }
+    }
+
+    let mut journal = match config.journal {
+        Some(ref journal_file) => match journal::Journal::open(journal_file) {
+            Ok(journal) => Some(journal),
+            Err(err) => {
+                return Err(ApplyError {
+                    summary,
+                    reason: format!("Failed to open journal {journal_file:?}: {err}"),
+                })
+            }
+        },
+        None => None,
+    };
+
+    let mut errors = 0;
+    for (source_file, dest_file) in changes {
         if source_file == dest_file {
-            files_skipped += 1;
-        } else if dest_file.exists() {
-            eprintln!("File already exists! Not taking action! {dest_file:?}");
-            files_skipped += 1;
-        } else {
-            println!("moving {source_file:?} to {dest_file:?}");
-            match std::fs::rename(source_file, dest_file) {
-                Ok(()) => {
-                    println!("Ok");
-                    files_changed += 1
+            summary.skip(&source_file, &dest_file, "unchanged");
+            continue;
+        }
+        let dest_file = match destination_taken(&source_file, &dest_file) {
+            false => dest_file,
+            true if config.rename_if_newer && !is_newer(&source_file, &dest_file) => {
+                eprintln!("Destination is newer, not taking action! {dest_file:?}");
+                summary.conflict(&source_file, &dest_file, "destination is newer");
+                continue;
+            }
+            true => match config.conflict_strategy() {
+                ConflictStrategy::Skip => {
+                    eprintln!("File already exists! Not taking action! {dest_file:?}");
+                    summary.conflict(&source_file, &dest_file, "destination exists");
+                    continue;
+                }
+                ConflictStrategy::Error => {
+                    summary.conflict(&source_file, &dest_file, "destination exists");
+                    summary.print(config);
+                    return Err(ApplyError {
+                        summary,
+                        reason: format!(
+                            "File already exists, stopping (--rename-conflicts error): {dest_file:?}"
+                        ),
+                    });
+                }
+                ConflictStrategy::Suffix => resolve_conflict(
+                    &dest_file,
+                    config.rename_conflicts,
+                    &config.conflict_suffix_format,
+                ),
+                ConflictStrategy::Overwrite => {
+                    match config.backup_suffix {
+                        Some(ref suffix) => {
+                            let mut backup_file = dest_file.clone().into_os_string();
+                            backup_file.push(suffix);
+                            let backup_file = PathBuf::from(backup_file);
+                            status!(config, "backing up {dest_file:?} to {backup_file:?}");
+                            if let Err(err) = std::fs::rename(&dest_file, &backup_file) {
+                                eprintln!("Failed to back up, not taking action! {err:?}");
+                                summary.skip(
+                                    &source_file,
+                                    &dest_file,
+                                    &format!("failed to back up destination: {err}"),
+                                );
+                                continue;
+                            }
+                        }
+                        None => eprintln!("Warning: overwriting {dest_file:?}"),
+                    }
+                    dest_file
+                }
+            },
+        };
+        if let Some(ref backup_dir) = config.backup_dir {
+            match backup_to_dir(
+                &source_file,
+                backup_dir,
+                config.backup_preserve_structure,
+                config.preserve_timestamps,
+            ) {
+                Ok(backup_file) => {
+                    status!(config, "backed up {source_file:?} to {backup_file:?}")
                 }
-                Err(err) => eprintln!("Failed to rename: {err:?}"),
+                Err(err) => {
+                    eprintln!("Failed to back up {source_file:?}, not taking action! {err:?}");
+                    summary.skip(
+                        &source_file,
+                        &dest_file,
+                        &format!("failed to back up source: {err}"),
+                    );
+                    continue;
+                }
+            }
+        }
+        if let (true, Some(parent)) = (
+            config.hash_buckets.is_some() || config.date_dirs.is_some() || config.create_dirs,
+            dest_file.parent(),
+        ) {
+            if let Err(err) = std::fs::create_dir_all(parent) {
+                eprintln!("Failed to create {parent:?}, not taking action! {err:?}");
+                summary.fail(&source_file, &dest_file, &err);
+                continue;
+            }
+        }
+        // written just before renaming, so a pending entry without a result means it stopped part way through
+        if let Some(ref mut journal) = journal {
+            if let Err(err) = journal.write(&journal::JournalEntry::Pending {
+                source: &source_file,
+                dest: &dest_file,
+                timestamp: journal::timestamp(),
+            }) {
+                eprintln!("Failed to write to journal: {err:?}");
+            }
+        }
+        status!(config, "moving {source_file:?} to {dest_file:?}");
+        let result = std::fs::rename(&source_file, &dest_file);
+        if let Some(ref mut journal) = journal {
+            let entry = match result {
+                Ok(()) => journal::JournalEntry::Done {
+                    source: &source_file,
+                    dest: &dest_file,
+                },
+                Err(ref err) => journal::JournalEntry::Failed {
+                    source: &source_file,
+                    dest: &dest_file,
+                    error: err.to_string(),
+                },
             };
+            if let Err(err) = journal.write(&entry) {
+                eprintln!("Failed to write to journal: {err:?}");
+            }
         }
-    });
-    println!("Files changed: {}", files_changed);
-    println!("Files skipped: {}", files_skipped);
+        match result {
+            Ok(()) => {
+                status!(config, "Ok");
+                summary.renamed(source_file, dest_file);
+            }
+            Err(err) => {
+                eprintln!("Failed to rename: {err:?}");
+                summary.fail(&source_file, &dest_file, &err);
+                if config.fail_fast {
+                    summary.print(config);
+                    return Err(ApplyError {
+                        summary,
+                        reason: format!(
+                            "Stopping after failing to rename {source_file:?} (--fail-fast): {err}"
+                        ),
+                    });
+                }
+                errors += 1;
+                if config
+                    .max_errors
+                    .is_some_and(|max_errors| errors >= max_errors)
+                {
+                    eprintln!("Too many errors, aborting.");
+                    summary.print(config);
+                    return Err(ApplyError {
+                        summary,
+                        reason: format!("Stopping after {errors} failed renames (--max-errors)"),
+                    });
+                }
+            }
+        };
+    }
+    summary.print(config);
+    Ok(summary)
 }
 
-fn main() {
-    let args = Args::parse();
+/// prints any cycles in the changes, returning true if there were some
+fn report_cycles(changes: &[(PathBuf, PathBuf)]) -> bool {
+    let cycles = cycles::detect_cycles(&[cycles::RenamePlan { changes }]);
+    if cycles.is_empty() {
+        return false;
+    }
+    eprintln!("These renames go round in a circle, so they can't be done one at a time:");
+    cycles.iter().for_each(|cycle| eprintln!("  {cycle}"));
+    eprintln!("Rename one of each to a temporary name first, then try again.");
+    true
+}
+
+/// checks the number of matched paths against the --assert-*count arguments
+fn check_match_count(args: &Args, count: usize) -> Result<(), String> {
+    if let Some(expected) = args.assert_count {
+        if count != expected {
+            return Err(format!(
+                "Expected exactly {expected} matches, found {count}"
+            ));
+        }
+    }
+    if let Some(min) = args.assert_min_count {
+        if count < min {
+            return Err(format!("Expected at least {min} matches, found {count}"));
+        }
+    }
+    if let Some(max) = args.assert_max_count {
+        if count > max {
+            return Err(format!("Expected at most {max} matches, found {count}"));
+        }
+    }
+    Ok(())
+}
+
+/// exits with code 2 for --fail-on-no-match, or an error listing the matched paths if the --assert-*count arguments aren't met
+fn enforce_match_count(args: &Args, count: usize, paths: &[PathBuf]) {
+    if args.fail_on_no_match && count == 0 {
+        eprintln!("Didn't match any paths!");
+        process::exit(2);
+    }
+    if let Err(err) = check_match_count(args, count) {
+        eprintln!("{err}");
+        eprintln!("Matched paths:");
+        paths
+            .iter()
+            .for_each(|path| eprintln!("{}", path.to_string_lossy()));
+        process::exit(1);
+    }
+}
+
+/// previews the changes from --rename-csv or --from-json and asks before applying them, unless --yes is set
+///
+/// the ones with a destination that's taken are only kept when --rename-conflicts says what to do with them
+fn confirm_planned_changes(changes: Changes, args: &Args, config: &Config) -> Option<Changes> {
+    let (blocked, changes): (Changes, Changes) = changes
+        .into_iter()
+        .partition(|(source, dest)| source != dest && destination_taken(source, dest));
+    let changeset = ChangeSet {
+        changes,
+        blocked,
+        ..Default::default()
+    };
+    preview::print_preview(&changeset, config);
+    let mut changes = changeset.changes;
+    if config.conflict_strategy() != ConflictStrategy::Skip {
+        changes.extend(changeset.blocked);
+    }
+    if args.yes {
+        return Some(changes);
+    }
+    let prompt = match config.simulate {
+        true => format!("Simulate changes to {} files?", changes.len()),
+        false => format!("Apply changes to {} files?", changes.len()),
+    };
+    let term = Term::stderr();
+    if !term.is_term() {
+        eprintln!("Not applying the changes without a terminal to ask on, use --yes to apply them");
+        process::exit(1);
+    }
+    Confirm::with_theme(&color_scheme::dialoguer_theme(config.color_scheme))
+        .with_prompt(prompt)
+        .default(false)
+        .interact_on(&term)
+        .unwrap_or(false)
+        .then_some(changes)
+}
+
+/// reads one path per line from stdin, for when the file selection's been done by another tool
+fn get_stdin_paths() -> Vec<PathBuf> {
+    std::io::stdin()
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match PathBuf::from(&line).canonicalize() {
+            Ok(path) => Some(path),
+            Err(err) => {
+                eprintln!("Skipping {line}: {err:?}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// asks for a regex, up and down go through the history for `field` when --interactive-regex-history is set
+fn prompt_regex(
+    prompt: &str,
+    initial_text: &str,
+    config: &Config,
+    history: Option<&mut history::RegexHistory>,
+    field: history::HistoryField,
+) -> std::io::Result<String> {
+    let theme = color_scheme::dialoguer_theme(config.color_scheme);
+    let mut field_history = history.map(|history| history.field(field));
+    let mut input = Input::<String>::with_theme(&theme);
+    input.with_prompt(prompt).with_initial_text(initial_text);
+    if let Some(ref mut field_history) = field_history {
+        input.history_with(field_history);
+    }
+    input.interact_text()
+}
 
-    if get_files(&args).is_none() {
+/// asks for a name and saves the flags and regexes from this session as a profile in the config file
+fn export_session(raw_args: &[String], args: &Args, config: &Config) {
+    let Some(config_file) = args.config.clone().or_else(profile::default_config_file) else {
+        eprintln!("Couldn't work out where the config file is");
+        return;
+    };
+    let name =
+        match Input::<String>::with_theme(&color_scheme::dialoguer_theme(config.color_scheme))
+            .with_prompt("Preset name")
+            .with_initial_text(args.profile.clone().unwrap_or_default())
+            .interact_text()
+        {
+            Ok(name) => name,
+            Err(err) => {
+                eprintln!("Failed to get the preset name: {err:?}");
+                return;
+            }
+        };
+    let session = profile::session_profile(raw_args, config);
+    match profile::export_profile(&config_file, &name, session) {
+        Ok(toml) => {
+            println!("Saved to {}:\n", config_file.display());
+            println!("{toml}");
+        }
+        Err(err) => eprintln!("{err}"),
+    }
+}
+
+/// true when stdout is carrying machine-readable output, which status and progress text mustn't get mixed into
+fn stdout_is_machine_readable(args: &Args) -> bool {
+    matches!(
+        args.preview_format,
+        PreviewFormat::Json | PreviewFormat::Csv
+    ) || args.output_script_powershell.as_deref() == Some("-")
+        || args.report_unchanged.as_deref() == Some("-")
+        || args.output_json.as_deref() == Some("-")
+        || args.dry_run_diff
+}
+
+/// the whole command line tool, `main.rs` just calls this
+fn run() {
+    let raw_args = match profile::args_with_profile(std::env::args().collect()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    };
+    let args = Args::parse_from(&raw_args);
+
+    let incremental = match args.rename_incremental {
+        true => match incremental::Incremental::load(&raw_args[1..]) {
+            Ok(incremental) => Some(incremental),
+            Err(err) => {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+        },
+        false => None,
+    };
+
+    if let Some(Commands::CheckRegex { ref pattern, tree }) = args.command {
+        match check_regex::check_regex(pattern, tree) {
+            true => process::exit(0),
+            false => process::exit(1),
+        }
+    }
+
+    if !args.stdin_paths
+        && args.test_file.is_none()
+        && args.rename_csv.is_none()
+        && args.from_json.is_none()
+        && get_files(args.filepath.as_deref().unwrap_or(".")).is_none()
+    {
         println!("No files found :(");
         process::exit(1);
     }
 
-    let mut config = Config::default();
+    let mut builder = Config::builder()
+        .verbose(args.verbose, args.verbose_all)
+        .strict(args.strict)
+        .summarize_unmatched(args.summarize_unmatched)
+        .simulate(args.simulate)
+        .rename_conflicts(match args.overwrite {
+            true => ConflictStrategy::Overwrite,
+            false => args.rename_conflicts.unwrap_or_default(),
+        })
+        .conflict_suffix_format(&args.conflict_suffix_format)
+        .rename_if_newer(args.rename_if_newer_than_dest)
+        .backup_suffix(args.backup_suffix.clone())
+        .backup_dir(args.backup_dir.clone(), args.backup_preserve_structure)
+        .preserve_timestamps(args.preserve_timestamps)
+        .normalize(args.normalize)
+        .encode_spaces(args.encode_spaces)
+        .use_pager(!args.no_pager)
+        .scroll_preview(args.scroll_preview)
+        .escape_special(args.escape_special)
+        .wrap_regex(&args.wrap_regex_prefix, &args.wrap_regex_suffix)
+        .match_whole_path(args.match_whole_path)
+        .create_dirs(args.create_dirs)
+        .replace_nth(args.regex_replace_nth)
+        .rename_part(args.rename_part)
+        .match_case_variants(args.match_case_variants)
+        .fuzzy_match(args.fuzzy_match.clone(), args.fuzzy_threshold)
+        .invert_match(args.invert_match)
+        .skip_regex_validation(args.skip_regex_validation)
+        .max_filename_length(args.max_filename_length)
+        .truncate_marker(args.truncate_marker)
+        .fail_fast(args.fail_fast)
+        .max_errors(args.max_errors)
+        .match_depth(args.match_depth)
+        .preview_limit(args.interactive_preview_limit)
+        .preview_count(args.preview_n.0)
+        .sanitize(args.sanitize)
+        .journal(args.journal.clone())
+        .output_rename_log(args.output_rename_log.clone())
+        .rename_conflict_log(args.rename_conflict_log.clone())
+        .rename_by_content_type(args.rename_by_content_type)
+        .hash_buckets(args.rename_by_hash_bucket)
+        .date_dirs(
+            match (args.rename_by_modified_date, args.rename_by_created_date) {
+                (true, _) => Some(DateSource::Modified),
+                (_, true) => Some(DateSource::Created),
+                _ => None,
+            },
+        )
+        .append_to_stem(args.append_prefix.clone(), args.append_suffix.clone())
+        .strip_from_stem(args.strip_prefix.clone(), args.strip_suffix.clone())
+        .affixes_only(
+            args.pattern.is_none() && args.substitute.is_none() && args.rename_template.is_none(),
+        )
+        .rename_template(args.rename_template.is_some())
+        .audio_tags(args.rename_by_audio_tags)
+        .pdf_metadata(args.rename_by_pdf_metadata)
+        .counter(
+            CounterFormat {
+                start: args.counter_start,
+                step: args.counter_step,
+                width: args.counter_width,
+                number_format: args.format_number,
+            },
+            args.rename_by_sequence_in_dir,
+        )
+        .match_type(args.match_type)
+        .modified_after(
+            incremental
+                .as_ref()
+                .and_then(|incremental| incremental.last_run),
+        )
+        .preview_format(args.preview_format)
+        .status_to_stderr(stdout_is_machine_readable(&args))
+        .preview_cols(args.preview_cols.clone())
+        .color_scheme(args.color_scheme);
+
+    if let Some(ref fd_args) = args.from_fd_args {
+        let fd_args: Vec<&str> = fd_args.split_whitespace().collect();
+        match fd::parse_fd_args(&fd_args).and_then(|parsed| {
+            fd::translate_fd_args(&fd_args)
+                .map(|matcher_string| (parsed.match_type, matcher_string))
+        }) {
+            Ok((match_type, matcher_string)) => {
+                if let Some(match_type) = match_type {
+                    builder = builder.match_type(match_type);
+                }
+                builder = builder.matcher(&matcher_string);
+            }
+            Err(err) => {
+                eprintln!("Failed to parse fd arguments: {err}");
+                process::exit(1);
+            }
+        }
+    }
+
+    if args.rename_extension_only {
+        if let (Some(find), Some(replace)) = (&args.find, &args.replace) {
+            let find = find.trim_start_matches('.');
+            builder = builder
+                .matcher(&format!(r".*\.{}$", regex::escape(find)))
+                .rename_extension_only(find, replace.trim_start_matches('.'));
+        }
+    }
+
+    if let Some(ref ext_map) = args.ext_map {
+        match ext_map::ExtMap::parse(ext_map).and_then(|ext_map| {
+            ext_map
+                .rules()
+                .map(|rules| (ext_map.matcher_string(), rules))
+                .map_err(|err| format!("{err:?}"))
+        }) {
+            Ok((matcher_string, rules)) => {
+                builder = builder.matcher(&matcher_string).ext_map(rules)
+            }
+            Err(err) => {
+                eprintln!("Failed to parse --ext-map: {err}");
+                process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(feature = "lua")]
+    if let Some(ref script_file) = args.script_file {
+        match std::fs::read_to_string(script_file) {
+            Ok(script) => builder = builder.lua_script(script),
+            Err(err) => {
+                eprintln!("Failed to read script file {script_file:?}: {err:?}");
+                process::exit(1);
+            }
+        }
+    }
 
     if let Some(ref matcher_string) = args.regex_matcher {
-        config.matcher_string = matcher_string.to_string();
+        builder = builder.matcher(matcher_string);
     }
 
     if let Some(ref pattern) = args.pattern {
-        config.renamer_string = pattern.to_string();
+        builder = builder.renamer(pattern);
     }
 
     if let Some(ref substitute) = args.substitute {
-        config.replacement_string = substitute.to_string();
+        builder = builder.replacement(substitute);
     }
 
-    let base_path = match PathBuf::from_str(&args.filepath).unwrap().canonicalize() {
+    for exclude_glob in args.exclude_glob.iter() {
+        builder = builder.exclude_glob(exclude_glob);
+    }
+
+    if let Some(ref only_in) = args.only_in {
+        let only_in = Path::new(args.filepath.as_deref().unwrap_or(".")).join(only_in);
+        match only_in.canonicalize() {
+            Ok(only_in) => builder = builder.only_in(Some(only_in)),
+            Err(err) => {
+                eprintln!("Failed to find --only-in directory {only_in:?}: {err:?}");
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(ref rename_template) = args.rename_template {
+        builder = builder.replacement(rename_template);
+    }
+
+    let mut config = match builder.show_unchanged(true).build() {
         Ok(val) => val,
         Err(err) => {
-            eprintln!("Error finding path: {err:?}");
+            eprintln!("{err}");
             process::exit(1);
         }
     };
-    let base_path = base_path.to_string_lossy();
 
-    loop {
-        config.matcher_string = match Input::<String>::with_theme(&ColorfulTheme::default())
-            .with_prompt("Enter your file-matching regex")
-            .with_initial_text(config.matcher_string.clone())
-            .interact_text()
-        {
+    if let Some(ref test_file) = args.test_file {
+        match test_file::test_file(test_file, &config) {
+            true => process::exit(0),
+            false => process::exit(1),
+        }
+    }
+
+    let planned_changes = match (&args.rename_csv, &args.from_json) {
+        (Some(rename_csv), _) => Some(rename_csv::read_rename_csv(rename_csv)),
+        (None, Some(from_json)) => Some(rename_json::read_rename_json(from_json)),
+        (None, None) => None,
+    };
+    if let Some(planned_changes) = planned_changes {
+        let changes = match planned_changes {
             Ok(val) => val,
             Err(err) => {
-                eprintln!("Input error: {err:?}");
-                config.matcher_string
+                eprintln!("{err}");
+                process::exit(1);
             }
         };
+        if report_cycles(&changes) {
+            process::exit(1);
+        }
+        let changes = match confirm_planned_changes(changes, &args, &config) {
+            Some(changes) => changes,
+            None => process::exit(0),
+        };
+        let changes = order_for_apply(changes, &args);
+        let apply_start = Instant::now();
+        let result = apply_changes(changes, &config);
+        if args.stats_after {
+            match result {
+                Ok(ref summary) => summary.print_summary(apply_start.elapsed()),
+                Err(ref err) => err.summary.print_summary(apply_start.elapsed()),
+            }
+        }
+        match result {
+            Ok(_) => process::exit(0),
+            Err(err) => {
+                eprintln!(
+                    "{} ({} files were renamed before stopping)",
+                    err.reason,
+                    err.summary.applied.len()
+                );
+                process::exit(1);
+            }
+        }
+    }
 
-        let matcher_regex = match get_matcher_regex(&config.matcher_string) {
+    if args.list_matched {
+        // not get_matcher_regex, it'd print to stdout along with the paths
+        let matcher_regex = match Regex::new(&config.matcher_pattern()) {
             Ok(val) => val,
             Err(err) => {
-                eprintln!("###################################################");
                 eprintln!("Failed to parse matcher regex: {err:?}");
-                eprintln!("###################################################");
-                continue;
+                process::exit(1);
             }
         };
+        let mut matched_paths = get_matched_paths_with_timeout(&args, &config, matcher_regex);
+        matched_paths.sort();
+        matched_paths
+            .iter()
+            .for_each(|path| println!("{}", path.to_string_lossy()));
+        process::exit(0);
+    }
 
-        let matched_paths = get_matched_paths(&args, matcher_regex);
-        if matched_paths.is_empty() {
-            println!("Didn't match any paths!");
-            continue;
+    let base_path = match PathBuf::from(args.filepath.as_deref().unwrap_or(".")).canonicalize() {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("Error finding path: {err:?}");
+            process::exit(1);
         }
+    };
+    let base_path = base_path.to_string_lossy();
 
-        println!("Matched {} paths!", matched_paths.len());
-        let first_num = match matched_paths.len() >= 10 {
-            true => 10,
-            false => matched_paths.len(),
-        };
-        if matched_paths.len() > 1 {
-            println!("First {first_num} paths:");
-            matched_paths[0..first_num].iter().for_each(|path| {
-                let path_str = path.to_str().unwrap();
-                println!("{path_str}");
-            });
-        } else {
-            println!("Matched: {:?}", matched_paths.first().unwrap());
+    let mut stdin_paths = match args.stdin_paths {
+        true => {
+            let paths = get_stdin_paths();
+            if paths.is_empty() {
+                println!("No paths were given on stdin :(");
+                process::exit(1);
+            }
+            Some(paths)
         }
+        false => None,
+    };
 
-        config.renamer_string = match Input::<String>::with_theme(&ColorfulTheme::default())
-            .with_prompt("Enter a regex to grab the bit you want to rename")
-            .with_initial_text(config.renamer_string.clone())
-            .interact_text()
-        {
-            Ok(val) => val,
+    #[cfg(feature = "tui")]
+    if args.tui {
+        let files: Vec<PathBuf> = get_files(args.filepath.as_deref().unwrap_or("."))
+            .map(|paths| paths.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+        match tui::run_tui(&files, &base_path, &config) {
+            Ok(Some(result)) => {
+                config.matcher_string = result.matcher;
+                config.renamer_string = result.renamer;
+                config.replacement_string = result.replacement;
+            }
+            Ok(None) => process::exit(0),
             Err(err) => {
-                eprintln!("Input error: {err:?}");
-                config.renamer_string
+                eprintln!("TUI error: {err:?}");
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut timings = Timings::default();
+
+    let mut previewed = false;
+
+    // what each apply in this session renamed, so the last one can be undone
+    let mut undo_stack: Vec<Vec<(PathBuf, PathBuf)>> = Vec::new();
+    // set after an apply without errors, so the session can be exported as a profile
+    let mut applied_ok = false;
+    let mut regex_history = args
+        .interactive_regex_history
+        .then(history::RegexHistory::load);
+    // once there's been an apply the CSV has its results, so it's not overwritten with the next round's pending changes
+    let mut csv_written = false;
+
+    // the matcher string the renamer suggestion was last made for
+    let mut suggested_for: Option<String> = None;
+
+    loop {
+        if stdin_paths.is_none() {
+            status!(config, "Scanning: {base_path}");
+        }
+        // nothing's prompted for in the shorthand modes, so the paths go from the scan straight into the pairs
+        let streamed =
+            match stdin_paths.is_none() && !config.uses_regexes() && !args.interactive_mode {
+                true => {
+                    let matcher_regex = match get_matcher_regex(&config) {
+                        Ok(val) => val,
+                        Err(err) => {
+                            eprintln!("Failed to build extension matcher: {err:?}");
+                            process::exit(1);
+                        }
+                    };
+                    status!(config, "Finding files...");
+                    let scan_start = Instant::now();
+                    let file_path = args.filepath.as_deref().unwrap_or(".");
+                    let streamed = with_regex_timeout(&args, || {
+                        let mut listing = MatchListing::new(&args, &config);
+                        let paths = get_matched_paths(file_path, &config, matcher_regex)
+                            .inspect(|path| listing.add(path));
+                        let changeset = change_pairs(paths, &base_path, None, &config);
+                        (listing, changeset)
+                    });
+                    timings.scan_and_match = scan_start.elapsed();
+                    Some(streamed)
+                }
+                false => None,
+            };
+        let matched_paths = match stdin_paths {
+            Some(ref paths) => paths.clone(),
+            None if streamed.is_some() => Vec::new(),
+            None => {
+                if config.fuzzy_match.is_none() {
+                    config.matcher_string = match prompt_regex(
+                        "Enter your file-matching regex",
+                        &config.matcher_string,
+                        &config,
+                        regex_history.as_mut(),
+                        history::HistoryField::Matcher,
+                    ) {
+                        Ok(val) => val,
+                        Err(err) => {
+                            eprintln!("Input error: {err:?}");
+                            config.matcher_string
+                        }
+                    };
+                    if !config.escape_special && looks_like_filename(&config.matcher_string) {
+                        let escape = Confirm::with_theme(&color_scheme::dialoguer_theme(
+                            config.color_scheme,
+                        ))
+                        .with_prompt("(Escape special characters?)")
+                        .default(false)
+                        .interact()
+                        .unwrap_or(false);
+                        if escape {
+                            config.matcher_string = regex::escape(&config.matcher_string);
+                        }
+                    }
+                }
+
+                let matcher_regex = match get_matcher_regex(&config) {
+                    Ok(val) => val,
+                    Err(err) => {
+                        eprintln!("###################################################");
+                        eprintln!("Failed to parse matcher regex: {err:?}");
+                        eprintln!("###################################################");
+                        continue;
+                    }
+                };
+
+                status!(config, "Finding files...");
+                let scan_start = Instant::now();
+                let matched_paths =
+                    get_matched_paths_with_timeout(&args, &config, matcher_regex.clone());
+                timings.scan_and_match = scan_start.elapsed();
+
+                // only suggest when the user hasn't told us what they want, and the matcher's changed
+                if !matched_paths.is_empty()
+                    && args.pattern.is_none()
+                    && args.substitute.is_none()
+                    && args.rename_template.is_none()
+                    && suggested_for.as_ref() != Some(&config.matcher_string)
+                {
+                    if let Some((renamer, replacement)) =
+                        suggest_renamer(&matcher_regex, &matched_paths)
+                    {
+                        config.renamer_string = renamer;
+                        config.replacement_string = replacement;
+                    }
+                    suggested_for = Some(config.matcher_string.clone());
+                }
+                matched_paths
             }
         };
 
-        let renamer_regex = match get_renamer_regex(&config.renamer_string) {
-            Ok(val) => val,
-            Err(err) => {
-                eprintln!("###################################################");
-                eprintln!("Failed to parse renamer regex: {err:?}");
-                eprintln!("###################################################");
-                continue;
+        let (match_count, listed) = match streamed {
+            Some((ref listing, _)) => (listing.count, listing.paths.as_slice()),
+            None => (matched_paths.len(), matched_paths.as_slice()),
+        };
+        enforce_match_count(&args, match_count, listed);
+        if match_count == 0 {
+            status!(config, "Didn't match any paths!");
+            // only the prompts can change what's matched, otherwise there's nothing to retry
+            match stdin_paths.is_none() && (config.uses_regexes() || args.interactive_mode) {
+                true => continue,
+                false => process::exit(0),
+            }
+        }
+
+        status!(config, "Matched {match_count} paths!");
+        let first_num = config
+            .preview_count
+            .map_or(match_count, |count| count.min(match_count));
+        match (first_num, listed.first()) {
+            (0, _) | (_, None) => {}
+            (_, Some(path)) if match_count == 1 => status!(config, "Matched: {path:?}"),
+            _ => {
+                status!(config, "First {first_num} paths:");
+                listed[0..first_num].iter().for_each(|path| {
+                    status!(config, "{}", path.to_string_lossy());
+                });
+            }
+        }
+
+        let renamer_regex = match config.uses_renamer_regex() {
+            false => None,
+            true => {
+                config.renamer_string = match prompt_regex(
+                    "Enter a regex to grab the bit you want to rename",
+                    &config.renamer_string,
+                    &config,
+                    regex_history.as_mut(),
+                    history::HistoryField::Renamer,
+                ) {
+                    Ok(val) => val,
+                    Err(err) => {
+                        eprintln!("Input error: {err:?}");
+                        config.renamer_string
+                    }
+                };
+
+                let renamer_regex = match get_renamer_regex(&config) {
+                    Ok(val) => Some(val),
+                    Err(err) => {
+                        eprintln!("###################################################");
+                        eprintln!("Failed to parse renamer regex: {err:?}");
+                        eprintln!("###################################################");
+                        continue;
+                    }
+                };
+
+                if let (Some(ref renamer_regex), false) = (&renamer_regex, config.rename_template) {
+                    status!(
+                        config,
+                        "{}",
+                        show_capture_preview(
+                            renamer_regex,
+                            &matched_paths,
+                            &config.replacement_string
+                        )
+                    );
+                }
+
+                let replacement_prompt = match config.rename_template {
+                    true => "Enter your rename template",
+                    false => "Enter your replacement string",
+                };
+                let term = Term::stdout();
+                let replacement = match (term.is_term(), &renamer_regex) {
+                    (true, Some(renamer_regex)) => LiveInput::new(replacement_prompt, |text| {
+                        live_preview(&matched_paths, renamer_regex, text, &config)
+                    })
+                    .with_initial_text(&config.replacement_string)
+                    .interact_on(&term),
+                    _ => Input::<String>::new()
+                        .with_prompt(replacement_prompt)
+                        .allow_empty(true)
+                        .with_initial_text(config.replacement_string.clone())
+                        .interact_text(),
+                };
+                config.replacement_string = match replacement {
+                    Ok(val) => val,
+                    Err(err) => {
+                        eprintln!("Input error, reusing the previous string. Error: {err:?}");
+                        config.replacement_string.clone()
+                    }
+                };
+                if !config.rename_template {
+                    replacement_warnings(&config.replacement_string)
+                        .iter()
+                        .for_each(|warning| eprintln!("Warning: {warning}"));
+                }
+                renamer_regex
             }
         };
 
-        config.replacement_string = match Input::<String>::new()
-            .with_prompt("Enter your replacement string")
-            .allow_empty(true)
-            .with_initial_text(config.replacement_string.clone())
-            .interact_text()
+        let rename_start = Instant::now();
+        let changeset = match streamed {
+            Some((_, changeset)) => changeset,
+            None => change_pairs(matched_paths, &base_path, renamer_regex.clone(), &config),
+        };
+
+        timings.rename = rename_start.elapsed();
+
+        let explain =
+            (args.verbose && changeset.changes.len() <= VERBOSE_FILE_LIMIT) || args.verbose_all;
+        if let (true, false, Some(renamer_regex)) =
+            (explain, config.rename_template, &renamer_regex)
         {
-            Ok(val) => val,
-            Err(err) => {
-                eprintln!("Input error, reusing the previous string. Error: {err:?}");
-                config.replacement_string.clone()
+            changeset
+                .changes
+                .iter()
+                .filter(|(source, dest)| source != dest)
+                .for_each(|(source, dest)| {
+                    status!(
+                        config,
+                        "{}",
+                        explain_change(
+                            source,
+                            dest,
+                            renamer_regex,
+                            &config.replacement_string,
+                            config.replace_nth
+                        )
+                    )
+                });
+        }
+
+        // show_unchanged can only be switched off by the menu after this, so only check before the first preview
+        if !previewed {
+            validate::validate_config(&config)
+                .iter()
+                .for_each(|warning| eprintln!("Warning: {warning}"));
+            previewed = true;
+        }
+
+        if let Some(ref report_file) = args.report_unchanged {
+            let unchanged: Vec<&PathBuf> = changeset
+                .changes
+                .iter()
+                .filter(|(source, dest)| source == dest)
+                .map(|(source, _)| source)
+                .collect();
+            let result = match report_file.as_str() {
+                "-" => write_path_list(&unchanged, args.print0, &mut std::io::stdout()),
+                report_file => std::fs::File::create(report_file)
+                    .and_then(|mut file| write_path_list(&unchanged, args.print0, &mut file)),
+            };
+            if let Err(err) = result {
+                eprintln!("Failed to write unchanged paths to {report_file}: {err:?}");
+                process::exit(1);
             }
-        };
+        }
 
-        let changes = get_change_pairs(
-            matched_paths,
-            base_path.clone().into(),
-            renamer_regex,
-            &config.replacement_string,
-        );
+        if args.report_would_overwrite {
+            if changeset.blocked.is_empty() {
+                println!("No renames would overwrite existing files.");
+                process::exit(0);
+            }
+            println!("Would overwrite existing files:");
+            preview::review_table("Source", &changeset.blocked).printstd();
+            process::exit(3);
+        }
 
-        changes.iter().for_each(|(path_str, result)| {
-            println!(
-                "- {}\n+ {}",
-                path_str.to_str().unwrap_or("error"),
-                result.to_str().unwrap_or("error")
-            )
-        });
+        if changeset.is_all_unchanged() {
+            status!(
+                config,
+                "All files already match the target naming convention. Nothing to do."
+            );
+            // stay in the menu if there's an apply to undo
+            if undo_stack.is_empty() {
+                process::exit(0);
+            }
+        }
+
+        if let Some(ref script_file) = args.output_script_powershell {
+            let result = match script_file.as_str() {
+                "-" => {
+                    powershell::write_powershell_script(&changeset.changes, &mut std::io::stdout())
+                }
+                script_file => std::fs::File::create(script_file).and_then(|mut file| {
+                    powershell::write_powershell_script(&changeset.changes, &mut file)
+                }),
+            };
+            match result {
+                Ok(()) => {
+                    if args.timing {
+                        timings.print(&config);
+                    }
+                    process::exit(0)
+                }
+                Err(err) => {
+                    eprintln!("Failed to write PowerShell script {script_file}: {err:?}");
+                    process::exit(1);
+                }
+            }
+        }
+
+        if let Some(ref json_file) = args.output_json {
+            let result = match json_file.as_str() {
+                "-" => preview::render_preview(
+                    &changeset,
+                    PreviewFormat::Json,
+                    &config,
+                    &mut std::io::stdout(),
+                ),
+                json_file => std::fs::File::create(json_file).and_then(|mut file| {
+                    preview::render_preview(&changeset, PreviewFormat::Json, &config, &mut file)
+                }),
+            };
+            match result {
+                Ok(()) => {
+                    if args.timing {
+                        timings.print(&config);
+                    }
+                    process::exit(0)
+                }
+                Err(err) => {
+                    eprintln!("Failed to write JSON {json_file}: {err:?}");
+                    process::exit(1);
+                }
+            }
+        }
+
+        if args.dry_run_diff {
+            if let Err(err) =
+                dry_run_diff::write_dry_run_diff(&changeset.changes, &mut std::io::stdout())
+            {
+                eprintln!("Failed to write the diff: {err:?}");
+                process::exit(1);
+            }
+            if args.timing {
+                timings.print(&config);
+            }
+            process::exit(0);
+        }
+
+        let render_start = Instant::now();
+        match args.sample {
+            Some(amount) if amount < changeset.changes.len() => {
+                status!(
+                    config,
+                    "Previewing {amount} of {} changes picked at random, applying changes uses all of them",
+                    changeset.changes.len()
+                );
+                preview::print_preview(&sample_changeset(&changeset, amount, args.seed), &config);
+            }
+            _ => preview::print_preview(&changeset, &config),
+        }
+        timings.render = render_start.elapsed();
+        // the blocked changes go in too, so the CSV shows they were skipped
+        let planned_changes: Vec<(PathBuf, PathBuf)> = match args.output_csv {
+            Some(ref csv_file) if !csv_written => {
+                let planned_changes: Vec<(PathBuf, PathBuf)> = changeset
+                    .changes
+                    .iter()
+                    .chain(changeset.blocked.iter())
+                    .cloned()
+                    .collect();
+                if let Err(err) = output_csv::write_changes_csv(csv_file, &planned_changes, None) {
+                    eprintln!("Failed to write CSV {csv_file:?}: {err:?}");
+                }
+                planned_changes
+            }
+            _ => Vec::new(),
+        };
+        let mut changes = changeset.changes;
+        if config.conflict_strategy() != ConflictStrategy::Skip {
+            changes.extend(changeset.blocked);
+        }
 
         let mut menu_items = vec!["Change regexes"];
 
-        let menu_apply = format!("Apply changes to {} files", changes.len());
+        let menu_apply = match config.simulate {
+            true => format!("Simulate changes to {} files", changes.len()),
+            false => format!("Apply changes to {} files", changes.len()),
+        };
         menu_items.push(&menu_apply);
 
         if config.show_unchanged {
@@ -302,21 +2242,99 @@ fn main() {
         } else {
             menu_items.push("Show unchanged files");
         }
+        let menu_undo = match undo_stack.last() {
+            Some(applied) => format!("Undo last apply ({} files)", applied.len()),
+            None => String::new(),
+        };
+        if !undo_stack.is_empty() {
+            menu_items.push(&menu_undo);
+        }
+        if applied_ok {
+            menu_items.push("Export this session as a preset");
+        }
         menu_items.push("Quit without making changes");
 
-        let menu_result = Select::with_theme(&ColorfulTheme::default())
-            .items(&menu_items)
-            .default(0)
-            .interact_on_opt(&Term::stderr())
-            .map_err(|err| {
-                eprintln!("Menu error: {err:?}");
-                -1
-            })
-            .unwrap();
+        let menu_result = match args.yes {
+            true => Some(1),
+            false => Select::with_theme(&color_scheme::dialoguer_theme(config.color_scheme))
+                .items(&menu_items)
+                .default(0)
+                .interact_on_opt(&Term::stderr())
+                .map_err(|err| {
+                    eprintln!("Menu error: {err:?}");
+                    -1
+                })
+                .unwrap(),
+        };
 
         match menu_result {
+            Some(1) if report_cycles(&changes) => {
+                if args.yes {
+                    process::exit(1);
+                }
+            }
             Some(1) => {
-                apply_changes(changes);
+                let changes = order_for_apply(changes, &args);
+                let apply_start = Instant::now();
+                let result = apply_changes(changes, &config);
+                let failed = result.is_err();
+                timings.apply = apply_start.elapsed();
+                if args.timing {
+                    timings.print(&config);
+                }
+                if args.stats_after {
+                    match result {
+                        Ok(ref summary) => summary.print_summary(timings.apply),
+                        Err(ref err) => err.summary.print_summary(timings.apply),
+                    }
+                }
+                if let (Some(ref csv_file), false) = (&args.output_csv, csv_written) {
+                    csv_written = true;
+                    let summary = match result {
+                        Ok(ref summary) => summary,
+                        Err(ref err) => &err.summary,
+                    };
+                    if let Err(err) = output_csv::write_changes_csv(
+                        csv_file,
+                        &planned_changes,
+                        Some(&summary.log),
+                    ) {
+                        eprintln!("Failed to write CSV {csv_file:?}: {err:?}");
+                    }
+                }
+                let applied = match result {
+                    // a simulation, or an apply that didn't rename anything, isn't a run to count from or export
+                    Ok(summary) if config.simulate || summary.applied.is_empty() => summary.applied,
+                    Ok(summary) => {
+                        applied_ok = true;
+                        if let Some(ref incremental) = incremental {
+                            if let Err(err) = incremental.record() {
+                                eprintln!("Failed to save the time of this run: {err:?}");
+                            }
+                        }
+                        summary.applied
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "{} ({} files were renamed before stopping)",
+                            err.reason,
+                            err.summary.applied.len()
+                        );
+                        err.summary.applied
+                    }
+                };
+                if args.yes {
+                    process::exit(match failed {
+                        true => 1,
+                        false => 0,
+                    });
+                }
+                if let (Some(ref mut paths), false) = (&mut stdin_paths, config.simulate) {
+                    follow_renames(paths, &applied);
+                }
+                if !applied.is_empty() {
+                    undo_stack.push(applied);
+                }
             }
             Some(2) => {
                 config.show_unchanged = !config.show_unchanged;
@@ -325,9 +2343,59 @@ fn main() {
                     false => println!("Hiding unchanged files"),
                 };
             }
-            Some(3) => process::exit(0),
+            Some(menu_result) if applied_ok && menu_result == menu_items.len() - 2 => {
+                export_session(&raw_args, &args, &config)
+            }
+            Some(3) if !undo_stack.is_empty() => {
+                if let Some(applied) = undo_stack.pop() {
+                    let restored = undo_changes(applied);
+                    if let Some(ref mut paths) = stdin_paths {
+                        follow_renames(paths, &restored);
+                    }
+                }
+            }
+            Some(menu_result) if menu_result == menu_items.len() - 1 => {
+                if args.timing {
+                    timings.print(&config);
+                }
+                process::exit(0)
+            }
             Some(menu_result) => eprintln!("Selected #{menu_result} {}", menu_items[menu_result]),
             None => eprintln!("?"),
         }
     }
 }
+
+fn main() {
+    run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renamed_paths_are_followed_in_order() {
+        let mut paths = vec![
+            PathBuf::from("/photos/a/x.jpeg"),
+            PathBuf::from("/photos/a/y.jpeg"),
+            PathBuf::from("/photos/z.jpeg"),
+        ];
+        let renamed = vec![
+            (
+                PathBuf::from("/photos/a/x.jpeg"),
+                PathBuf::from("/photos/a/x.jpg"),
+            ),
+            (PathBuf::from("/photos/a"), PathBuf::from("/photos/b")),
+        ];
+        follow_renames(&mut paths, &renamed);
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/photos/b/x.jpg"),
+                PathBuf::from("/photos/b/y.jpeg"),
+                PathBuf::from("/photos/z.jpeg"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Finding the paths to rename, see [get_matched_paths].
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+
+use glob::{glob, Paths};
+use prettytable::{row, Table};
+use regex::Regex;
+
+use crate::config::Config;
+use crate::fuzzy;
+
+/// Above this many files, verbose output is suppressed unless --verbose-all is set
+pub const VERBOSE_FILE_LIMIT: usize = 1000;
+
+/// glob wants forward slashes, so convert Windows paths, see [windows_glob_base]
+fn glob_base(filepath: &str) -> String {
+    match cfg!(windows) {
+        true => windows_glob_base(filepath),
+        false => filepath.to_string(),
+    }
+}
+
+/// converts a Windows path to forward slashes, including the `\\?\` and UNC forms
+fn windows_glob_base(filepath: &str) -> String {
+    let filepath = match filepath.strip_prefix(r"\\?\UNC\") {
+        Some(unc_path) => format!(r"\\{unc_path}"),
+        None => filepath
+            .strip_prefix(r"\\?\")
+            .unwrap_or(filepath)
+            .to_string(),
+    };
+    filepath.replace('\\', "/")
+}
+
+/// every path under `file_path`, recursively
+pub fn get_files(file_path: &str) -> Option<Paths> {
+    let filepath = glob_base(file_path);
+    let pattern = match filepath.ends_with('/') {
+        true => format!("{}**/*", filepath),
+        false => format!("{}/**/*", filepath),
+    };
+
+    match glob(&pattern) {
+        Ok(val) => Some(val),
+        Err(error) => {
+            eprintln!(
+                "Failed to parse directory pattern ({}): {error:?}",
+                &pattern
+            );
+            None
+        }
+    }
+}
+
+/// gets the path component `depth` levels up from the end, 0 is the filename, 1 the parent directory and so on
+fn extract_component(path: &Path, depth: usize) -> Option<&OsStr> {
+    match path.components().rev().nth(depth)? {
+        Component::Normal(component) => Some(component),
+        _ => None,
+    }
+}
+
+/// what was found while scanning, shown with --verbose
+#[derive(Debug, Default)]
+struct ScanStats {
+    pub total_entries: usize,
+    pub files: usize,
+    pub dirs: usize,
+    pub symlinks: usize,
+    pub errors: usize,
+}
+
+impl ScanStats {
+    fn add(&mut self, path: &Path) {
+        self.total_entries += 1;
+        match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_symlink() => self.symlinks += 1,
+            Ok(metadata) if metadata.is_dir() => self.dirs += 1,
+            Ok(_) => self.files += 1,
+            Err(_) => self.errors += 1,
+        }
+    }
+
+    fn print(&self, matched: usize, config: &Config) {
+        status!(
+            config,
+            "Scanned {} entries: {} files, {} dirs, {} symlinks, {} errors. Matched {} files.",
+            with_thousands(self.total_entries),
+            with_thousands(self.files),
+            with_thousands(self.dirs),
+            with_thousands(self.symlinks),
+            with_thousands(self.errors),
+            with_thousands(matched)
+        );
+    }
+}
+
+/// formats a number with commas between the thousands, eg 3,421
+fn with_thousands(number: usize) -> String {
+    let digits = number.to_string();
+    let mut result = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    result
+}
+
+/// true if the path is the right type for --match-type and new enough for --rename-incremental
+pub fn is_candidate(path: &Path, config: &Config) -> bool {
+    config.match_type.matches(path)
+        && match config.modified_after {
+            Some(modified_after) => path
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified > modified_after),
+            None => true,
+        }
+}
+
+/// runs the matcher (or the fuzzy query) over the path, or the component picked by --match-depth
+pub fn matches_regex(path: &Path, config: &Config, matcher_regex: &Regex) -> bool {
+    let is_match_str = |haystack: &str| match config.fuzzy_match {
+        Some(ref query) => fuzzy::fuzzy_score(query, haystack) > config.fuzzy_threshold,
+        None => matcher_regex.is_match(haystack),
+    };
+    let is_match = match config.match_depth {
+        Some(depth) => extract_component(path, depth)
+            .and_then(|component| component.to_str())
+            .is_some_and(is_match_str),
+        None => is_match_str(&path.to_string_lossy()),
+    };
+    is_match != config.invert_match
+}
+
+/// true if the path's been left out by --exclude-glob or isn't under --only-in
+pub fn is_excluded(path: &Path, config: &Config) -> bool {
+    let excluded = config.exclude_globs.iter().any(|pattern| {
+        pattern.matches_path(path)
+            || path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| pattern.matches(name))
+    });
+    excluded
+        || match config.only_in {
+            Some(ref only_in) => !path
+                .canonicalize()
+                .is_ok_and(|path| path.starts_with(only_in)),
+            None => false,
+        }
+}
+
+/// the paths under `file_path` that the matcher picks out and the config doesn't exclude
+///
+/// the paths are found lazily as it's iterated, the stats and summaries are printed once it's used up
+pub fn get_matched_paths<'a>(
+    file_path: &str,
+    config: &'a Config,
+    matcher_regex: Regex,
+) -> MatchedPaths<'a> {
+    MatchedPaths {
+        files: get_files(file_path),
+        config,
+        matcher_regex,
+        stats: ScanStats::default(),
+        matched: 0,
+        verbose_lines: Vec::new(),
+        verbose_suppressed: false,
+        unmatched: UnmatchedSummary::default(),
+        finished: false,
+    }
+}
+
+/// the iterator from [get_matched_paths]
+pub struct MatchedPaths<'a> {
+    files: Option<Paths>,
+    config: &'a Config,
+    matcher_regex: Regex,
+    stats: ScanStats,
+    matched: usize,
+    /// the ✓ and ✗ lines, held back until it's clear there aren't more than [VERBOSE_FILE_LIMIT] paths
+    verbose_lines: Vec<String>,
+    verbose_suppressed: bool,
+    unmatched: UnmatchedSummary,
+    finished: bool,
+}
+
+impl MatchedPaths<'_> {
+    fn is_match(&mut self, path: &Path) -> bool {
+        if !is_candidate(path, self.config) {
+            return false;
+        }
+        let is_match = matches_regex(path, self.config, &self.matcher_regex);
+        self.report(path, is_match);
+        is_match && !is_excluded(path, self.config)
+    }
+
+    /// the verbose line for a path, printed straight away with --verbose-all
+    fn report(&mut self, path: &Path, is_match: bool) {
+        let config = self.config;
+        if !(config.verbose || config.verbose_all) || self.verbose_suppressed {
+            return;
+        }
+        let line = match is_match {
+            true => format!("✓ {}", path.to_string_lossy()),
+            false => format!("✗ {}", path.to_string_lossy()),
+        };
+        match config.verbose_all {
+            true => status!(config, "{line}"),
+            false => self.verbose_lines.push(line),
+        }
+    }
+
+    /// counts the path, and stops the verbose output once there's been too many
+    fn scanned(&mut self, path: &Path) {
+        self.stats.add(path);
+        let config = self.config;
+        if config.verbose
+            && !config.verbose_all
+            && !self.verbose_suppressed
+            && self.stats.total_entries - self.stats.errors > VERBOSE_FILE_LIMIT
+        {
+            status!(
+                config,
+                "Suppressing verbose for >{VERBOSE_FILE_LIMIT} files; use --verbose-all to override."
+            );
+            self.verbose_suppressed = true;
+            self.verbose_lines = Vec::new();
+        }
+    }
+
+    fn finish(&mut self) {
+        let config = self.config;
+        self.verbose_lines
+            .drain(..)
+            .for_each(|line| status!(config, "{line}"));
+        if config.verbose || config.verbose_all {
+            self.stats.print(self.matched, config);
+        }
+        if config.summarize_unmatched {
+            match self.unmatched.count {
+                0 => status!(config, "Every path scanned was matched."),
+                count => {
+                    status!(config, "{count} paths weren't matched:");
+                    print_status_table(&self.unmatched.table(), config);
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for MatchedPaths<'_> {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        if self.finished {
+            return None;
+        }
+        while let Some(entry) = self.files.as_mut().and_then(Iterator::next) {
+            let path = match entry {
+                Ok(path) => path,
+                Err(err) => {
+                    self.stats.total_entries += 1;
+                    self.stats.errors += 1;
+                    eprintln!("Error: {err:?}");
+                    if self.config.strict {
+                        eprintln!("Not continuing because of --strict");
+                        process::exit(1);
+                    }
+                    continue;
+                }
+            };
+            self.scanned(&path);
+            if self.is_match(&path) {
+                self.matched += 1;
+                return Some(path);
+            }
+            // only the right type of path, so directories don't show up when matching files
+            if self.config.summarize_unmatched && self.config.match_type.matches(&path) {
+                self.unmatched.add(path);
+            }
+        }
+        self.finished = true;
+        self.finish();
+        None
+    }
+}
+
+/// prints a table that's part of the status output, so it follows [crate::status] to stderr when needed
+fn print_status_table(table: &Table, config: &Config) {
+    match config.status_to_stderr {
+        true => {
+            if let Err(err) = table.print(&mut std::io::stderr()) {
+                eprintln!("Failed to write table: {err:?}");
+            }
+        }
+        false => {
+            table.printstd();
+        }
+    }
+}
+
+/// the paths that weren't matched, grouped by extension with how many there are and one of them
+#[derive(Default)]
+struct UnmatchedSummary {
+    count: usize,
+    by_extension: BTreeMap<String, (usize, PathBuf)>,
+}
+
+impl UnmatchedSummary {
+    fn add(&mut self, path: PathBuf) {
+        self.count += 1;
+        let extension = match path.extension() {
+            Some(ext) => format!(".{}", ext.to_string_lossy()),
+            None => "(none)".to_string(),
+        };
+        self.by_extension.entry(extension).or_insert((0, path)).0 += 1;
+    }
+
+    /// the extensions, most common first
+    fn table(&self) -> Table {
+        let mut by_extension: Vec<_> = self.by_extension.iter().collect();
+        by_extension.sort_by(|(_, (a, _)), (_, (b, _))| b.cmp(a));
+
+        let mut table = Table::new();
+        table.set_titles(row![b->"Extension", b->"Count", b->"Example"]);
+        by_extension
+            .into_iter()
+            .for_each(|(extension, (count, example))| {
+                table.add_row(row![extension, r->count, example.to_string_lossy()]);
+            });
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_glob_base_converts_separators() {
+        assert_eq!(windows_glob_base(r"C:\photos\"), "C:/photos/");
+        assert_eq!(windows_glob_base(r"photos\2024"), "photos/2024");
+    }
+
+    #[test]
+    fn windows_glob_base_handles_unc_paths() {
+        assert_eq!(windows_glob_base(r"\\server\share\x"), "//server/share/x");
+        assert_eq!(
+            windows_glob_base(r"\\?\UNC\server\share\x"),
+            "//server/share/x"
+        );
+    }
+
+    #[test]
+    fn windows_glob_base_strips_the_verbatim_prefix() {
+        assert_eq!(windows_glob_base(r"\\?\C:\x"), "C:/x");
+    }
+}
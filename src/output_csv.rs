@@ -0,0 +1,43 @@
+//! `--output-csv`, the planned changes as a CSV for reviewing in a spreadsheet, rewritten with what happened after an apply.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::rename_log::LogEntry;
+
+#[derive(Debug, Serialize)]
+struct CsvChange<'a> {
+    source: &'a Path,
+    destination: &'a Path,
+    changed: bool,
+    status: String,
+}
+
+/// writes a row for each change, the status is "pending" until there's an apply log to take it from
+///
+/// changes which aren't in the log weren't tried, eg because the destination exists, so they're "skipped"
+pub fn write_changes_csv(
+    csv_file: &Path,
+    changes: &[(PathBuf, PathBuf)],
+    log: Option<&[LogEntry]>,
+) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(csv_file)?;
+    for (source, dest) in changes {
+        let status = match log {
+            None => "pending".to_string(),
+            Some(log) => match log.iter().find(|entry| &entry.source == source) {
+                Some(entry) => entry.status.to_string(),
+                None => "skipped".to_string(),
+            },
+        };
+        writer.serialize(CsvChange {
+            source,
+            destination: dest,
+            changed: source != dest,
+            status,
+        })?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! `--rename-by-pdf-metadata`, which makes the title, author, subject and creation date from a PDF's
+//! info dictionary available to `--rename-template`, eg `{{pdf_author}} - {{pdf_title}}.pdf`
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use lopdf::Document;
+
+/// the template variables which come from the info dictionary
+pub const PDF_METADATA: [&str; 4] = [
+    "pdf_title",
+    "pdf_author",
+    "pdf_creation_date",
+    "pdf_subject",
+];
+
+/// turns a PDF date like `D:20230405120000+00'00'` into `2023-04-05`, or None if it isn't one
+fn pdf_date(date: &str) -> Option<String> {
+    let digits = date.strip_prefix("D:").unwrap_or(date);
+    let digits = digits.get(..8)?;
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..8]
+    ))
+}
+
+/// reads the fields the PDF has, empty ones are left out and they're made safe to use in a filename
+pub fn read_pdf_metadata(path: &Path) -> Result<HashMap<String, String>, String> {
+    let metadata = Document::load_metadata(path)
+        .map_err(|err| format!("Couldn't read the metadata of {path:?}: {err}"))?;
+    let values = [
+        ("pdf_title", metadata.title),
+        ("pdf_author", metadata.author),
+        (
+            "pdf_creation_date",
+            metadata
+                .creation_date
+                .map(|date| pdf_date(&date).unwrap_or(date)),
+        ),
+        ("pdf_subject", metadata.subject),
+    ];
+    Ok(values
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let value = value.filter(|value| !value.trim().is_empty())?;
+            Some((
+                name.to_string(),
+                crate::change_pairs::windows_safe(value.trim()),
+            ))
+        })
+        .collect())
+}
@@ -0,0 +1,67 @@
+//! `--output-script-powershell`, which writes the renames out as a PowerShell script to run later.
+
+use std::io::Write;
+use std::path::Path;
+
+/// a double-quoted PowerShell string with backslash separators, escaping the characters PowerShell expands
+fn quote(path: &Path) -> String {
+    let mut quoted = String::from("\"");
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '/' => quoted.push('\\'),
+            '`' | '"' | '$' => {
+                quoted.push('`');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// a `Rename-Item` for each change, or `Move-Item` when it changes directory, stopping at the first error
+pub fn write_powershell_script(
+    changes: &[(std::path::PathBuf, std::path::PathBuf)],
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    writeln!(writer, "$ErrorActionPreference = \"Stop\"")?;
+    for (source, dest) in changes.iter().filter(|(source, dest)| source != dest) {
+        match (source.parent() == dest.parent(), dest.file_name()) {
+            (true, Some(filename)) => writeln!(
+                writer,
+                "Rename-Item -LiteralPath {} -NewName {}",
+                quote(source),
+                quote(Path::new(filename))
+            )?,
+            _ => writeln!(
+                writer,
+                "Move-Item -LiteralPath {} -Destination {}",
+                quote(source),
+                quote(dest)
+            )?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separators_are_backslashes() {
+        assert_eq!(
+            quote(Path::new("/photos/holiday 2024.jpeg")),
+            r#""\photos\holiday 2024.jpeg""#
+        );
+    }
+
+    #[test]
+    fn expanded_characters_are_escaped() {
+        assert_eq!(
+            quote(Path::new("$cost \"`quoted`\".txt")),
+            r#""`$cost `"``quoted```".txt""#
+        );
+    }
+}
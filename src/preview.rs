@@ -0,0 +1,527 @@
+//! Showing the user what's going to be renamed before anything happens.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use chrono::{DateTime, Local};
+use dialoguer::console::{Key, Term};
+use prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE;
+use prettytable::{row, Attr, Cell, Row, Table};
+use serde::Serialize;
+
+use renamer::config::{ColorScheme, ConflictStrategy, PreviewColumn, PreviewFormat};
+use renamer::{ChangeSet, Config};
+
+use crate::{color_scheme, diff};
+
+/// the column's heading in the preview table
+fn column_title(column: PreviewColumn) -> &'static str {
+    match column {
+        PreviewColumn::Source => "Source",
+        PreviewColumn::Dest => "Replacement",
+        PreviewColumn::Size => "Size",
+        PreviewColumn::Modified => "Modified",
+        PreviewColumn::Ext => "Ext",
+        PreviewColumn::Changed => "Changed",
+    }
+}
+
+/// the column's cell for one change
+fn column_cell(column: PreviewColumn, source: &Path, dest: &Path) -> Cell {
+    match column {
+        PreviewColumn::Source => Cell::new(&diff::highlight_deletions(
+            &source.to_string_lossy(),
+            &dest.to_string_lossy(),
+        )),
+        PreviewColumn::Dest => Cell::new(&diff::diff_highlight(
+            &source.to_string_lossy(),
+            &dest.to_string_lossy(),
+        )),
+        PreviewColumn::Size => match source.metadata() {
+            Ok(metadata) => Cell::new(&format_size(metadata.len())).style_spec("r"),
+            Err(_) => Cell::new("?"),
+        },
+        PreviewColumn::Modified => {
+            match source.metadata().and_then(|metadata| metadata.modified()) {
+                Ok(modified) => Cell::new(
+                    &DateTime::<Local>::from(modified)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                ),
+                Err(_) => Cell::new("?"),
+            }
+        }
+        PreviewColumn::Ext => Cell::new(
+            &dest
+                .extension()
+                .map(|ext| ext.to_string_lossy())
+                .unwrap_or_default(),
+        ),
+        PreviewColumn::Changed => match source != dest {
+            true => Cell::new("✓").style_spec("Fg"),
+            false => Cell::new("✗").style_spec("Fr"),
+        },
+    }
+}
+/// a file size in bytes, KiB, MiB etc
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{size} {}", UNITS[unit]),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PreviewEntry<'a> {
+    source: &'a Path,
+    destination: &'a Path,
+    changed: bool,
+    /// the destination already exists
+    blocked: bool,
+}
+
+/// every change in the set which should be shown, with whether it's blocked
+fn preview_entries<'a>(changeset: &'a ChangeSet, config: &Config) -> Vec<PreviewEntry<'a>> {
+    changeset
+        .changes
+        .iter()
+        .map(|pair| (pair, false))
+        .chain(changeset.blocked.iter().map(|pair| (pair, true)))
+        .filter(|((source, dest), _)| config.show_unchanged || source != dest)
+        .map(|((source, dest), blocked)| PreviewEntry {
+            source,
+            destination: dest,
+            changed: source != dest,
+            blocked,
+        })
+        .collect()
+}
+
+/// writes the preview in the requested format, tables go out without colours or paging
+pub fn render_preview(
+    changes: &ChangeSet,
+    format: PreviewFormat,
+    config: &Config,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    match format {
+        PreviewFormat::Table => {
+            let (tables, _) = preview_tables(changes, config);
+            for table in tables {
+                table.print(writer)?;
+            }
+        }
+        PreviewFormat::List => {
+            for entry in preview_entries(changes, config) {
+                writeln!(
+                    writer,
+                    "{} → {}",
+                    entry.source.to_string_lossy(),
+                    entry.destination.to_string_lossy()
+                )?;
+            }
+        }
+        PreviewFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, &preview_entries(changes, config))?;
+            writeln!(writer)?;
+        }
+        PreviewFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for entry in preview_entries(changes, config) {
+                csv_writer.serialize(entry)?;
+            }
+            csv_writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// the main preview table, with a numbered row for each change that's being shown
+fn build_preview_table(changeset: &ChangeSet, cols: &[PreviewColumn], config: &Config) -> Table {
+    let mut table = Table::new();
+    table.set_titles(preview_titles(cols, config.color_scheme));
+    changeset
+        .changes
+        .iter()
+        .filter(|(source, dest)| config.show_unchanged || source != dest)
+        .enumerate()
+        .for_each(|(index, (source, dest))| {
+            let mut cells = vec![Cell::new(&(index + 1).to_string()).style_spec("r")];
+            cells.extend(cols.iter().map(|col| column_cell(*col, source, dest)));
+            table.add_row(Row::new(cells));
+        });
+    table
+}
+
+fn preview_titles(cols: &[PreviewColumn], color_scheme: Option<ColorScheme>) -> Row {
+    let title = |text: &str| {
+        let cell = Cell::new(text).style_spec("b");
+        match color_scheme {
+            Some(scheme) => {
+                cell.with_style(Attr::ForegroundColor(color_scheme::header_color(scheme)))
+            }
+            None => cell,
+        }
+    };
+    let mut titles = vec![title("#")];
+    titles.extend(cols.iter().map(|col| title(column_title(*col))));
+    Row::new(titles)
+}
+
+/// builds the table of changes, followed by tables of anything the user should review, and the main table's titles
+fn preview_tables(changeset: &ChangeSet, config: &Config) -> (Vec<Table>, Row) {
+    let titles = preview_titles(&config.preview_cols, config.color_scheme);
+    let table = build_preview_table(changeset, &config.preview_cols, config);
+
+    let mut tables = vec![table];
+
+    if !changeset.blocked.is_empty() {
+        let title = match config.rename_conflicts {
+            _ if config.rename_if_newer => "Will be overwritten if the source is newer",
+            ConflictStrategy::Skip => "Will be skipped (destination exists)",
+            ConflictStrategy::Overwrite => "Will be overwritten (destination exists)",
+            ConflictStrategy::Suffix => "Will get a numeric suffix (destination exists)",
+            ConflictStrategy::Error => "Will stop the apply (destination exists)",
+        };
+        tables.push(review_table(title, &changeset.blocked));
+    }
+    if !changeset.reserved_names.is_empty() {
+        let title = match config.sanitize {
+            true => "Reserved on Windows, will have _ appended",
+            false => "Will be skipped (reserved name on Windows)",
+        };
+        tables.push(review_table(title, &changeset.reserved_names));
+    }
+    if !changeset.truncated.is_empty() {
+        tables.push(review_table(
+            "Truncated to fit --max-filename-length",
+            &changeset.truncated,
+        ));
+    }
+    if !changeset.skipped_no_metadata.is_empty() {
+        tables.push(path_table(
+            "Will be skipped (missing metadata)",
+            &changeset.skipped_no_metadata,
+        ));
+    }
+    (tables, titles)
+}
+
+/// prints the planned changes, and anything that's going to be skipped
+pub fn print_preview(changeset: &ChangeSet, config: &Config) {
+    let writer: Option<Box<dyn Write>> = match config.preview_format {
+        PreviewFormat::Json | PreviewFormat::Csv => Some(Box::new(std::io::stdout())),
+        // stdout's carrying something else, eg the paths from --report-unchanged -
+        _ if config.status_to_stderr => Some(Box::new(std::io::stderr())),
+        PreviewFormat::List => Some(Box::new(std::io::stdout())),
+        PreviewFormat::Table => None,
+    };
+    if let Some(mut writer) = writer {
+        if let Err(err) = render_preview(changeset, config.preview_format, config, &mut writer) {
+            eprintln!("Failed to write preview: {err:?}");
+        }
+        return;
+    }
+
+    let (tables, titles) = preview_tables(changeset, config);
+    let term = Term::stdout();
+    let (term_height, _) = term.size();
+    let rows: usize = tables.iter().map(|table| table.len()).sum();
+    if config.scroll_preview && term.is_term() && rows > (term_height as usize).saturating_sub(5) {
+        match scroll_preview(changeset, config, &term) {
+            Ok(()) => {
+                tables.iter().skip(1).for_each(|table| {
+                    table.printstd();
+                });
+                return;
+            }
+            Err(err) => {
+                eprintln!("Failed to show the scrolling preview, printing instead: {err:?}")
+            }
+        }
+    }
+    if config.use_pager && term.is_term() && rows > (term_height as usize).saturating_sub(5) {
+        match page_tables(&tables) {
+            Ok(()) => return,
+            Err(err) => eprintln!("Failed to run pager, printing instead: {err:?}"),
+        }
+    }
+
+    let mut tables = tables.into_iter();
+    if let Some(table) = tables.next() {
+        match term.is_term() && config.preview_limit > 0 && table.len() > config.preview_limit {
+            true => print_paginated(&table, titles, config.preview_limit, &term),
+            false => {
+                table.printstd();
+            }
+        }
+    }
+    tables.for_each(|table| {
+        table.printstd();
+    });
+}
+
+/// what the scrolling preview's sorted by, `s` moves to the next one
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScrollSort {
+    Found,
+    Source,
+    Destination,
+}
+
+impl ScrollSort {
+    fn next(self) -> Self {
+        match self {
+            ScrollSort::Found => ScrollSort::Source,
+            ScrollSort::Source => ScrollSort::Destination,
+            ScrollSort::Destination => ScrollSort::Found,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ScrollSort::Found => "the order found",
+            ScrollSort::Source => "source",
+            ScrollSort::Destination => "destination",
+        }
+    }
+}
+
+/// the main preview table as lines of text, split into the title lines and the rest
+fn table_lines(
+    changeset: &ChangeSet,
+    config: &Config,
+    sort: ScrollSort,
+) -> (Vec<String>, Vec<String>) {
+    let mut changes = changeset.changes.clone();
+    match sort {
+        ScrollSort::Found => {}
+        ScrollSort::Source => changes.sort_by(|a, b| a.0.cmp(&b.0)),
+        ScrollSort::Destination => changes.sort_by(|a, b| a.1.cmp(&b.1)),
+    }
+    let sorted = ChangeSet {
+        changes,
+        ..Default::default()
+    };
+    let mut table = build_preview_table(&sorted, &config.preview_cols, config);
+    table.set_format(*FORMAT_NO_LINESEP_WITH_TITLE);
+    let mut output = Vec::new();
+    if let Err(err) = table.print(&mut output) {
+        eprintln!("Failed to render preview: {err:?}");
+    }
+    let mut lines: Vec<String> = String::from_utf8_lossy(&output)
+        .lines()
+        .map(|line| line.to_string())
+        .collect();
+    // the top border, titles and the line under them stay put while scrolling, the bottom border's dropped
+    lines.pop();
+    let body = lines.split_off(lines.len().min(3));
+    (lines, body)
+}
+
+/// shows the main preview table a screen at a time, scrolling with the arrow keys and page up/down until Enter
+fn scroll_preview(changeset: &ChangeSet, config: &Config, term: &Term) -> std::io::Result<()> {
+    let mut sort = ScrollSort::Found;
+    let (mut header, mut body) = table_lines(changeset, config, sort);
+    let mut offset = 0;
+    loop {
+        let height = (term.size().0 as usize)
+            .saturating_sub(header.len() + 2)
+            .max(1);
+        offset = offset.min(body.len().saturating_sub(height));
+
+        term.clear_screen()?;
+        header.iter().try_for_each(|line| term.write_line(line))?;
+        body.iter()
+            .skip(offset)
+            .take(height)
+            .try_for_each(|line| term.write_line(line))?;
+        term.write_line(&format!(
+            "Rows {}-{} of {}, sorted by {} | ↑/↓ PgUp/PgDn: scroll, s: sort by {}, Enter: done",
+            offset + 1,
+            (offset + height).min(body.len()),
+            body.len(),
+            sort.name(),
+            sort.next().name()
+        ))?;
+
+        match term.read_key()? {
+            Key::ArrowUp => offset = offset.saturating_sub(1),
+            Key::ArrowDown => offset += 1,
+            Key::PageUp => offset = offset.saturating_sub(height),
+            Key::PageDown => offset += height,
+            Key::Home => offset = 0,
+            Key::End => offset = body.len(),
+            Key::Char('s') => {
+                sort = sort.next();
+                (header, body) = table_lines(changeset, config, sort);
+                offset = 0;
+            }
+            Key::Enter => break,
+            _ => {}
+        }
+    }
+    term.clear_screen()
+}
+
+/// prints the table `limit` rows at a time, waiting for the user between pages
+fn print_paginated(table: &Table, titles: Row, limit: usize, term: &Term) {
+    let rows: Vec<Row> = table.row_iter().cloned().collect();
+    let mut chunks = rows.chunks(limit).peekable();
+    while let Some(chunk) = chunks.next() {
+        let mut page = Table::init(chunk.to_vec());
+        page.set_titles(titles.clone());
+        page.printstd();
+
+        if chunks.peek().is_none() {
+            break;
+        }
+        println!("Press Enter to see next {limit} or 'a' to see all, any other key to stop.");
+        match term.read_key() {
+            Ok(Key::Enter) => continue,
+            Ok(Key::Char('a')) => {
+                let mut rest = Table::init(chunks.flatten().cloned().collect());
+                rest.set_titles(titles);
+                rest.printstd();
+                break;
+            }
+            Ok(_) => break,
+            Err(err) => {
+                eprintln!("Failed to read key: {err:?}");
+                break;
+            }
+        }
+    }
+}
+
+/// builds a red table of changes the user should look at before applying
+pub fn review_table(title: &str, pairs: &[(PathBuf, PathBuf)]) -> Table {
+    let mut table = Table::new();
+    table.set_titles(row![Frb->title, Frb->"Destination"]);
+    pairs.iter().for_each(|(source, dest)| {
+        table.add_row(row![Fr->source.to_string_lossy(), Fr->dest.to_string_lossy()]);
+    });
+    table
+}
+
+/// a single column table of paths
+fn path_table(title: &str, paths: &[PathBuf]) -> Table {
+    let mut table = Table::new();
+    table.set_titles(row![Frb->title]);
+    paths.iter().for_each(|path| {
+        table.add_row(row![Fr->path.to_string_lossy()]);
+    });
+    table
+}
+
+/// sends the tables through $PAGER (or less), returning once the user quits it
+fn page_tables(tables: &[Table]) -> std::io::Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or("less".to_string());
+    let mut pager_args = pager.split_whitespace();
+    let pager_command = pager_args.next().unwrap_or("less");
+
+    let mut command = process::Command::new(pager_command);
+    command.args(pager_args).stdin(process::Stdio::piped());
+    // the preview's coloured, so make sure less passes the escape codes through
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "R");
+    }
+    let mut child = command.spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // the user quitting the pager early closes the pipe, which isn't an error
+        for table in tables {
+            if table.print(&mut stdin).is_err() {
+                break;
+            }
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changeset() -> ChangeSet {
+        ChangeSet {
+            changes: vec![
+                (
+                    PathBuf::from("/photos/a.jpeg"),
+                    PathBuf::from("/photos/a.jpg"),
+                ),
+                (
+                    PathBuf::from("/photos/b.jpg"),
+                    PathBuf::from("/photos/b.jpg"),
+                ),
+            ],
+            blocked: vec![(
+                PathBuf::from("/photos/c.jpeg"),
+                PathBuf::from("/photos/c.jpg"),
+            )],
+            ..Default::default()
+        }
+    }
+
+    fn render(format: PreviewFormat, config: &Config) -> String {
+        let mut output = Vec::new();
+        render_preview(&changeset(), format, config, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn table_has_each_change_and_the_blocked_table() {
+        let config = Config {
+            show_unchanged: true,
+            ..Default::default()
+        };
+        let output = render(PreviewFormat::Table, &config);
+        assert!(output.contains("Source"));
+        assert!(output.contains("Replacement"));
+        assert!(output.contains("/photos/a.jpeg"));
+        assert!(output.contains("/photos/b.jpg"));
+        assert!(output.contains("Will be skipped (destination exists)"));
+        assert!(output.contains("/photos/c.jpeg"));
+    }
+
+    #[test]
+    fn json_has_each_change_with_its_status() {
+        let config = Config {
+            show_unchanged: true,
+            ..Default::default()
+        };
+        let output: serde_json::Value =
+            serde_json::from_str(&render(PreviewFormat::Json, &config)).unwrap();
+        assert_eq!(
+            output,
+            serde_json::json!([
+                {"source": "/photos/a.jpeg", "destination": "/photos/a.jpg", "changed": true, "blocked": false},
+                {"source": "/photos/b.jpg", "destination": "/photos/b.jpg", "changed": false, "blocked": false},
+                {"source": "/photos/c.jpeg", "destination": "/photos/c.jpg", "changed": true, "blocked": true},
+            ])
+        );
+    }
+
+    #[test]
+    fn csv_has_a_header_and_skips_unchanged_when_hidden() {
+        let config = Config {
+            show_unchanged: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            render(PreviewFormat::Csv, &config),
+            "source,destination,changed,blocked\n\
+             /photos/a.jpeg,/photos/a.jpg,true,false\n\
+             /photos/c.jpeg,/photos/c.jpg,true,true\n"
+        );
+    }
+}
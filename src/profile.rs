@@ -0,0 +1,215 @@
+//! Named profiles from the config file, which are turned into command line flags before parsing.
+//!
+//! ```toml
+//! [profile.jpeg-to-jpg]
+//! matcher = '.*\.jpeg$'
+//! renamer = '(jpeg)'
+//! replacement = 'jpg'
+//! no_pager = true
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::CommandFactory;
+use toml::{Table, Value};
+
+use renamer::Config;
+
+use crate::Args;
+
+/// where profiles are read from, unless --config says otherwise
+pub fn default_config_file() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(config_dir) => PathBuf::from(config_dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("renamer").join("config.toml"))
+}
+
+/// the flag a profile setting maps to, the short names match the prompts
+fn flag_name(key: &str) -> String {
+    match key {
+        "matcher" => "regex-matcher".to_string(),
+        "renamer" => "pattern".to_string(),
+        "replacement" => "substitute".to_string(),
+        key => key.replace('_', "-"),
+    }
+}
+
+/// the value of `--name` or `--name=value` in the raw command line arguments
+fn find_flag(args: &[String], name: &str) -> Option<String> {
+    let prefix = format!("--{name}=");
+    args.iter()
+        .enumerate()
+        .find_map(|(index, arg)| match arg.strip_prefix(&prefix) {
+            Some(value) => Some(value.to_string()),
+            None if arg == &format!("--{name}") => args.get(index + 1).cloned(),
+            None => None,
+        })
+}
+
+/// turns the named profile into a list of flags
+pub fn profile_args(config_file: &Path, name: &str) -> Result<Vec<String>, String> {
+    let contents = std::fs::read_to_string(config_file)
+        .map_err(|err| format!("Failed to read config file {config_file:?}: {err}"))?;
+    let config: Table = toml::from_str(&contents)
+        .map_err(|err| format!("Failed to parse config file {config_file:?}: {err}"))?;
+    let profile = config
+        .get("profile")
+        .and_then(|profiles| profiles.get(name))
+        .and_then(|profile| profile.as_table())
+        .ok_or(format!("No [profile.{name}] in {config_file:?}"))?;
+
+    let command = Args::command();
+    let mut flags = Vec::new();
+    for (key, value) in profile {
+        let flag = flag_name(key);
+        if !command
+            .get_arguments()
+            .any(|arg| arg.get_long() == Some(flag.as_str()))
+        {
+            return Err(format!("Unknown setting {key} in [profile.{name}]"));
+        }
+        let values = match value {
+            Value::Array(values) => values.clone(),
+            value => vec![value.clone()],
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => flags.push(format!("--{flag}")),
+                Value::Boolean(false) => {}
+                Value::String(value) => flags.push(format!("--{flag}={value}")),
+                Value::Integer(value) => flags.push(format!("--{flag}={value}")),
+                Value::Float(value) => flags.push(format!("--{flag}={value}")),
+                other => {
+                    return Err(format!(
+                        "Setting {key} in [profile.{name}] can't be a {}",
+                        other.type_str()
+                    ))
+                }
+            }
+        }
+    }
+    Ok(flags)
+}
+
+/// puts the flags from `--profile` in front of the ones given, so the ones given win
+pub fn args_with_profile(args: Vec<String>) -> Result<Vec<String>, String> {
+    let Some(name) = find_flag(&args, "profile") else {
+        return Ok(args);
+    };
+    let config_file = match find_flag(&args, "config") {
+        Some(config_file) => PathBuf::from(config_file),
+        None => default_config_file().ok_or("Couldn't work out where the config file is")?,
+    };
+    let mut args = args.into_iter();
+    let mut result: Vec<String> = args.next().into_iter().collect();
+    result.extend(profile_args(&config_file, &name)?);
+    result.extend(args);
+    Ok(result)
+}
+
+/// the flags given on the command line as profile settings, with the regexes as they ended up in the session
+pub fn session_profile(args: &[String], config: &Config) -> Table {
+    let command = Args::command();
+    let matches = command.clone().get_matches_from(args);
+    let mut profile = Table::new();
+    for arg in command.get_arguments() {
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        let id = arg.get_id().as_str();
+        if [
+            "profile",
+            "config",
+            "regex_matcher",
+            "pattern",
+            "substitute",
+            "rename_template",
+        ]
+        .contains(&id)
+            || matches.value_source(id) != Some(ValueSource::CommandLine)
+        {
+            continue;
+        }
+        let value = match arg.get_action().takes_values() {
+            true => {
+                let values: Vec<Value> = matches
+                    .get_raw(id)
+                    .into_iter()
+                    .flatten()
+                    .map(|value| Value::String(value.to_string_lossy().to_string()))
+                    .collect();
+                match values.len() {
+                    1 => values[0].clone(),
+                    _ => Value::Array(values),
+                }
+            }
+            false => Value::Boolean(true),
+        };
+        profile.insert(long.replace('-', "_"), value);
+    }
+
+    if config.uses_regexes() && config.fuzzy_match.is_none() {
+        profile.insert(
+            "matcher".to_string(),
+            Value::String(config.matcher_string.clone()),
+        );
+    }
+    match config.rename_template {
+        true => {
+            profile.insert(
+                "rename_template".to_string(),
+                Value::String(config.replacement_string.clone()),
+            );
+        }
+        false if config.uses_renamer_regex() => {
+            profile.insert(
+                "renamer".to_string(),
+                Value::String(config.renamer_string.clone()),
+            );
+            profile.insert(
+                "replacement".to_string(),
+                Value::String(config.replacement_string.clone()),
+            );
+        }
+        false => {}
+    }
+    profile
+}
+
+/// saves the profile as `[profile.<name>]` in the config file, replacing one of the same name, and returns it as TOML
+pub fn export_profile(config_file: &Path, name: &str, profile: Table) -> Result<String, String> {
+    let mut config: Table = match std::fs::read_to_string(config_file) {
+        Ok(contents) => toml::from_str(&contents)
+            .map_err(|err| format!("Failed to parse config file {config_file:?}: {err}"))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Table::new(),
+        Err(err) => return Err(format!("Failed to read config file {config_file:?}: {err}")),
+    };
+    let profiles = config
+        .entry("profile")
+        .or_insert_with(|| Value::Table(Table::new()))
+        .as_table_mut()
+        .ok_or(format!("profile in {config_file:?} isn't a table"))?;
+    profiles.insert(name.to_string(), Value::Table(profile.clone()));
+
+    if let Some(parent) = config_file.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed to create {parent:?}: {err}"))?;
+    }
+    let contents = toml::to_string(&config)
+        .map_err(|err| format!("Failed to serialize config file {config_file:?}: {err}"))?;
+    std::fs::write(config_file, contents)
+        .map_err(|err| format!("Failed to write config file {config_file:?}: {err}"))?;
+
+    let mut exported = Table::new();
+    exported.insert(
+        "profile".to_string(),
+        Value::Table(Table::from_iter([(
+            name.to_string(),
+            Value::Table(profile),
+        )])),
+    );
+    toml::to_string(&exported).map_err(|err| format!("Failed to serialize profile {name}: {err}"))
+}
@@ -0,0 +1,43 @@
+//! `--rename-csv`, which reads source -> destination pairs from a CSV instead of working them out with regexes.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct CsvRename {
+    source: PathBuf,
+    destination: PathBuf,
+}
+
+/// reads the "source" and "destination" columns, failing if any of the sources don't exist
+pub fn read_rename_csv(csv_file: &Path) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut reader = csv::Reader::from_path(csv_file)
+        .map_err(|err| format!("Failed to open {csv_file:?}: {err}"))?;
+    let pairs = reader
+        .deserialize()
+        .map(|record| {
+            record
+                .map(|rename: CsvRename| (rename.source, rename.destination))
+                .map_err(|err| format!("Failed to read {csv_file:?}: {err}"))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    check_sources_exist(&pairs)?;
+    Ok(pairs)
+}
+
+/// fails listing the sources which don't exist, if there are any
+pub fn check_sources_exist(pairs: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    let missing: Vec<String> = pairs
+        .iter()
+        .filter(|(source, _)| !source.exists())
+        .map(|(source, _)| source.to_string_lossy().to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "These source files don't exist:\n{}",
+            missing.join("\n")
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! `--from-json`, which reads source -> destination pairs from a plan written with `--output-json` or `--preview-format json`.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct JsonRename {
+    source: PathBuf,
+    destination: PathBuf,
+}
+
+/// reads the changes from the plan, skipping unchanged ones and failing if any of the sources don't exist
+pub fn read_rename_json(json_file: &Path) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let contents = std::fs::read_to_string(json_file)
+        .map_err(|err| format!("Failed to open {json_file:?}: {err}"))?;
+    let renames: Vec<JsonRename> = serde_json::from_str(&contents)
+        .map_err(|err| format!("Failed to read {json_file:?}: {err}"))?;
+    let pairs: Vec<(PathBuf, PathBuf)> = renames
+        .into_iter()
+        .filter(|rename| rename.source != rename.destination)
+        .map(|rename| (rename.source, rename.destination))
+        .collect();
+    crate::rename_csv::check_sources_exist(&pairs)?;
+    Ok(pairs)
+}
@@ -0,0 +1,104 @@
+//! `--output-rename-log`, a tab-separated log of what each apply did, for opening in a spreadsheet.
+//!
+//! `--rename-conflict-log` writes just the changes skipped because the destination exists.
+
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, SecondsFormat};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogStatus {
+    Renamed,
+    Skipped,
+    Failed,
+}
+
+impl Display for LogStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogStatus::Renamed => write!(f, "renamed"),
+            LogStatus::Skipped => write!(f, "skipped"),
+            LogStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub status: LogStatus,
+    /// why it was skipped or failed
+    pub error_message: Option<String>,
+}
+
+impl LogEntry {
+    pub fn new(
+        source: &Path,
+        destination: &Path,
+        status: LogStatus,
+        error_message: Option<String>,
+    ) -> Self {
+        LogEntry {
+            timestamp: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+            status,
+            error_message,
+        }
+    }
+}
+
+const TSV_HEADER: &str = "timestamp\tsource\tdestination\tstatus\terror_message";
+
+/// writes one line per entry, tabs and newlines in the fields are replaced with spaces so the columns line up
+pub fn write_tsv_log(entries: &[LogEntry], writer: &mut impl Write) -> std::io::Result<()> {
+    let clean = |field: &str| field.replace(['\t', '\n', '\r'], " ");
+    for entry in entries {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}",
+            entry.timestamp,
+            clean(&entry.source.to_string_lossy()),
+            clean(&entry.destination.to_string_lossy()),
+            entry.status,
+            clean(entry.error_message.as_deref().unwrap_or_default())
+        )?;
+    }
+    Ok(())
+}
+
+/// adds the entries to the end of the log file, starting it with a header row if it's new
+pub fn append_tsv_log(log_file: &Path, entries: &[LogEntry]) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "{TSV_HEADER}")?;
+    }
+    write_tsv_log(entries, &mut file)
+}
+
+/// adds a `source<TAB>destination<TAB>reason` line for each of the conflicts to the end of the log file
+pub fn append_conflict_log(log_file: &Path, entries: &[LogEntry]) -> std::io::Result<()> {
+    let clean = |field: &str| field.replace(['\t', '\n', '\r'], " ");
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+    for entry in entries {
+        writeln!(
+            file,
+            "{}\t{}\t{}",
+            clean(&entry.source.to_string_lossy()),
+            clean(&entry.destination.to_string_lossy()),
+            clean(entry.error_message.as_deref().unwrap_or_default())
+        )?;
+    }
+    Ok(())
+}
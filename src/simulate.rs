@@ -0,0 +1,166 @@
+//! Runs a rename plan against an in-memory view of the filesystem, to check it's consistent before touching anything.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use renamer::change_pairs::destination_taken;
+
+#[derive(Debug)]
+pub enum SimulationProblem {
+    /// The same source file shows up more than once in the plan
+    SourceUsedTwice(PathBuf),
+    /// More than one source would end up at the same destination
+    DestinationCollision { source: PathBuf, dest: PathBuf },
+    /// The destination already exists and nothing moves it out of the way first
+    DestinationExists { source: PathBuf, dest: PathBuf },
+    /// The source was the destination of an earlier rename, so this moves a file that's already been moved
+    ChainedRename {
+        original: PathBuf,
+        source: PathBuf,
+        dest: PathBuf,
+    },
+    /// The source had already been moved away by an earlier rename
+    SourceMissing(PathBuf),
+}
+
+#[derive(Debug, Default)]
+pub struct SimulationReport {
+    /// virtual moves, dest -> source
+    pub moves: HashMap<PathBuf, PathBuf>,
+    pub unchanged: usize,
+    pub problems: Vec<SimulationProblem>,
+}
+
+impl SimulationReport {
+    pub fn print(&self) {
+        println!("Simulation report");
+        println!("Files that would be renamed: {}", self.moves.len());
+        println!("Files unchanged: {}", self.unchanged);
+        if self.problems.is_empty() {
+            println!("No problems found, the plan is consistent.");
+            return;
+        }
+        eprintln!("Problems found: {}", self.problems.len());
+        self.problems.iter().for_each(|problem| match problem {
+            SimulationProblem::SourceUsedTwice(source) => {
+                eprintln!("- source used more than once: {source:?}")
+            }
+            SimulationProblem::DestinationCollision { source, dest } => {
+                eprintln!("- {source:?} would collide at {dest:?} with another rename")
+            }
+            SimulationProblem::DestinationExists { source, dest } => {
+                eprintln!("- {source:?} would be skipped, {dest:?} already exists")
+            }
+            SimulationProblem::ChainedRename {
+                original,
+                source,
+                dest,
+            } => eprintln!(
+                "- {source:?} -> {dest:?} would move {original:?}, which was renamed to {source:?} earlier in the plan"
+            ),
+            SimulationProblem::SourceMissing(source) => {
+                eprintln!("- {source:?} has already been moved away earlier in the plan")
+            }
+        });
+    }
+}
+
+/// walks the changes in order, tracking where everything would end up without touching the filesystem
+pub fn simulate_changes(changes: &[(PathBuf, PathBuf)]) -> SimulationReport {
+    let mut report = SimulationReport::default();
+    let mut sources_seen: HashSet<&PathBuf> = HashSet::new();
+    // paths which have been moved away from and are now (virtually) empty
+    let mut vacated: HashSet<PathBuf> = HashSet::new();
+
+    for (source, dest) in changes {
+        if !sources_seen.insert(source) {
+            report
+                .problems
+                .push(SimulationProblem::SourceUsedTwice(source.clone()));
+            continue;
+        }
+        if source == dest {
+            report.unchanged += 1;
+            continue;
+        }
+        if vacated.contains(source) {
+            report
+                .problems
+                .push(SimulationProblem::SourceMissing(source.clone()));
+            continue;
+        }
+        if let Some(original) = report.moves.get(source) {
+            report.problems.push(SimulationProblem::ChainedRename {
+                original: original.clone(),
+                source: source.clone(),
+                dest: dest.clone(),
+            });
+            continue;
+        }
+        if report.moves.contains_key(dest) {
+            report
+                .problems
+                .push(SimulationProblem::DestinationCollision {
+                    source: source.clone(),
+                    dest: dest.clone(),
+                });
+            continue;
+        }
+        if destination_taken(source, dest) && !vacated.contains(dest) {
+            report.problems.push(SimulationProblem::DestinationExists {
+                source: source.clone(),
+                dest: dest.clone(),
+            });
+            continue;
+        }
+        vacated.remove(dest);
+        vacated.insert(source.clone());
+        report.moves.insert(dest.clone(), source.clone());
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consistent_plan_has_no_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        std::fs::write(path("b.txt"), "").unwrap();
+        // b.txt exists, but it's moved out of the way first
+        let report = simulate_changes(&[
+            (path("b.txt"), path("c.txt")),
+            (path("a.txt"), path("b.txt")),
+            (path("d.txt"), path("d.txt")),
+        ]);
+        assert!(report.problems.is_empty());
+        assert_eq!(report.moves.len(), 2);
+        assert_eq!(report.unchanged, 1);
+    }
+
+    #[test]
+    fn inconsistent_plans_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        std::fs::write(path("taken.txt"), "").unwrap();
+        let report = simulate_changes(&[
+            (path("a.txt"), path("b.txt")),
+            (path("a.txt"), path("c.txt")),
+            (path("d.txt"), path("b.txt")),
+            (path("b.txt"), path("e.txt")),
+            (path("f.txt"), path("taken.txt")),
+        ]);
+        assert!(matches!(
+            report.problems.as_slice(),
+            [
+                SimulationProblem::SourceUsedTwice(_),
+                SimulationProblem::DestinationCollision { .. },
+                SimulationProblem::ChainedRename { .. },
+                SimulationProblem::DestinationExists { .. },
+            ]
+        ));
+        assert_eq!(report.moves.len(), 1);
+    }
+}
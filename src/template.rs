@@ -0,0 +1,88 @@
+//! A minimal Handlebars-like template engine for `--rename-template`, eg `{{stem}}_copy.{{ext}}`
+
+use std::collections::HashMap;
+
+/// replaces each `{{name}}` in the template with its value from the context
+pub fn render_template(
+    template: &str,
+    context: &HashMap<String, String>,
+) -> Result<String, String> {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        let end = after_open
+            .find("}}")
+            .ok_or_else(|| format!("Unclosed {{{{ in template '{template}'"))?;
+        let name = after_open[..end].trim();
+        match context.get(name) {
+            Some(value) => result.push_str(value),
+            None => {
+                let mut known: Vec<&String> = context.keys().collect();
+                known.sort();
+                return Err(format!(
+                    "Unknown template variable '{name}', available: {}",
+                    known
+                        .iter()
+                        .map(|key| key.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ));
+            }
+        }
+        rest = &after_open[end + 2..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// the names of the `{{name}}` variables used in the template, stopping at an unclosed `{{`
+pub fn template_variables(template: &str) -> Vec<&str> {
+    let mut variables = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after_open = &rest[start + 2..];
+        let Some(end) = after_open.find("}}") else {
+            break;
+        };
+        variables.push(after_open[..end].trim());
+        rest = &after_open[end + 2..];
+    }
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> HashMap<String, String> {
+        HashMap::from([
+            ("stem".to_string(), "photo".to_string()),
+            ("ext".to_string(), "jpeg".to_string()),
+        ])
+    }
+
+    #[test]
+    fn variables_are_replaced() {
+        assert_eq!(
+            render_template("{{stem}}_copy.{{ ext }}", &context()).unwrap(),
+            "photo_copy.jpeg"
+        );
+        assert_eq!(render_template("plain", &context()).unwrap(), "plain");
+    }
+
+    #[test]
+    fn unknown_variables_list_the_available_ones() {
+        assert_eq!(
+            render_template("{{size}}", &context()).unwrap_err(),
+            "Unknown template variable 'size', available: ext, stem"
+        );
+    }
+
+    #[test]
+    fn unclosed_braces_are_an_error() {
+        assert!(render_template("{{stem", &context()).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+//! `--test-file`, which runs the matcher and renamer against one path without scanning or renaming anything.
+
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use renamer::{get_change_pairs, Config};
+
+use crate::get_renamer_regex;
+
+/// prints what the matcher and renamer make of `path`, returns false if either regex is broken
+pub fn test_file(path: &Path, config: &Config) -> bool {
+    let path_string = path.to_string_lossy();
+    println!("Testing {path_string}");
+
+    let matcher = match Regex::new(&config.matcher_pattern()) {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("Failed to parse matcher regex: {err}");
+            return false;
+        }
+    };
+    match matcher.is_match(&path_string) {
+        true => println!("Matcher {} matches", matcher.as_str()),
+        false => println!("Matcher {} doesn't match", matcher.as_str()),
+    }
+
+    let renamer = match get_renamer_regex(config) {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("Failed to parse renamer regex: {err}");
+            return false;
+        }
+    };
+    let filename = path
+        .file_name()
+        .map(|filename| filename.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match renamer.captures(&filename) {
+        Some(captures) => {
+            captures
+                .iter()
+                .enumerate()
+                .skip(1)
+                .for_each(|(index, group)| match group {
+                    Some(group) => println!("Capture group {index}: '{}'", group.as_str()),
+                    None => println!("Capture group {index}: (no match)"),
+                })
+        }
+        None => println!("Renamer doesn't match the filename"),
+    }
+
+    let base_path = path
+        .parent()
+        .map(|parent| parent.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let changeset = get_change_pairs(vec![PathBuf::from(path)], base_path, Some(renamer), config);
+    match (changeset.changes.first(), changeset.blocked.first()) {
+        (Some((_, dest)), _) if dest == path => println!("Result: unchanged"),
+        (Some((_, dest)), _) => println!("Result: {}", dest.to_string_lossy()),
+        (None, Some((_, dest))) => {
+            println!("Result: {} (already exists)", dest.to_string_lossy())
+        }
+        (None, None) => println!("Result: unchanged"),
+    }
+    true
+}
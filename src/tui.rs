@@ -0,0 +1,269 @@
+//! Full-screen interface for building the regexes with a live preview, behind the `tui` feature.
+//!
+//! Tab moves between the inputs, up/down scroll, Enter accepts the regexes and Esc quits.
+
+use std::path::PathBuf;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use regex::Regex;
+
+use renamer::matching::{is_candidate, is_excluded, matches_regex};
+use renamer::{get_change_pairs, Config};
+
+use crate::build_renamer_regex;
+
+/// what the user ended up with when they pressed Enter
+pub struct TuiResult {
+    pub matcher: String,
+    pub renamer: String,
+    pub replacement: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Matcher,
+    Renamer,
+    Replacement,
+}
+
+struct TuiState<'a> {
+    files: &'a [PathBuf],
+    base_path: &'a str,
+    /// the session's config with the regexes being edited, so the preview matches what applying does
+    config: Config,
+    focus: Field,
+    scroll: usize,
+    matched: Vec<&'a PathBuf>,
+    preview: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+impl TuiState<'_> {
+    fn input(&mut self) -> &mut String {
+        match self.focus {
+            Field::Matcher => &mut self.config.matcher_string,
+            Field::Renamer => &mut self.config.renamer_string,
+            Field::Replacement => &mut self.config.replacement_string,
+        }
+    }
+
+    /// re-runs the matcher and renamer against the file list after every keypress, the same way as outside the TUI
+    fn update(&mut self) {
+        self.error = None;
+        let config = &self.config;
+        self.matched = match Regex::new(&config.matcher_pattern()) {
+            Ok(matcher) => self
+                .files
+                .iter()
+                .filter(|path| {
+                    is_candidate(path, config)
+                        && matches_regex(path, config, &matcher)
+                        && !is_excluded(path, config)
+                })
+                .collect(),
+            Err(err) => {
+                self.error = Some(format!("Matcher: {err}"));
+                Vec::new()
+            }
+        };
+        let renamer = match config.uses_renamer_regex() {
+            false => Ok(None),
+            true => build_renamer_regex(config).map(Some),
+        };
+        self.preview = match renamer {
+            Ok(renamer) => {
+                let changeset = get_change_pairs(
+                    self.matched.iter().map(|path| path.to_path_buf()),
+                    self.base_path.to_string(),
+                    renamer,
+                    config,
+                );
+                let relative = |path: &PathBuf| path.to_string_lossy().replace(self.base_path, "");
+                changeset
+                    .changes
+                    .iter()
+                    .chain(changeset.blocked.iter())
+                    .map(|(source, dest)| (relative(source), relative(dest)))
+                    .collect()
+            }
+            Err(err) => {
+                self.error = Some(format!("Renamer: {err}"));
+                Vec::new()
+            }
+        };
+        self.scroll = self.scroll.min(self.matched.len().saturating_sub(1));
+    }
+}
+
+fn draw(frame: &mut Frame, state: &TuiState) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(rows[0]);
+
+    let files: Vec<ListItem> = state
+        .matched
+        .iter()
+        .skip(state.scroll)
+        .map(|path| ListItem::new(path.to_string_lossy().replace(state.base_path, "")))
+        .collect();
+    frame.render_widget(
+        List::new(files).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Matched files"),
+        ),
+        panes[0],
+    );
+
+    let preview: Vec<ListItem> = state
+        .preview
+        .iter()
+        .skip(state.scroll)
+        .map(|(source, dest)| {
+            let style = match source == dest {
+                true => Style::default().add_modifier(Modifier::DIM),
+                false => Style::default().fg(Color::Green),
+            };
+            ListItem::new(Line::styled(format!("{source} → {dest}"), style))
+        })
+        .collect();
+    frame.render_widget(
+        List::new(preview).block(Block::default().borders(Borders::ALL).title("Preview")),
+        panes[1],
+    );
+
+    let inputs = [
+        (
+            Field::Matcher,
+            "File matcher regex",
+            &state.config.matcher_string,
+            rows[1],
+        ),
+        (
+            Field::Renamer,
+            "Renamer regex",
+            &state.config.renamer_string,
+            rows[2],
+        ),
+        (
+            Field::Replacement,
+            "Replacement",
+            &state.config.replacement_string,
+            rows[3],
+        ),
+    ];
+    for (field, title, value, area) in inputs {
+        let style = match state.focus == field {
+            true => Style::default().fg(Color::Yellow),
+            false => Style::default(),
+        };
+        frame.render_widget(
+            Paragraph::new(value.as_str()).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(style)
+                    .title(title),
+            ),
+            area,
+        );
+    }
+
+    let changed = state
+        .preview
+        .iter()
+        .filter(|(source, dest)| source != dest)
+        .count();
+    let status = match state.error {
+        Some(ref error) => Line::styled(error.as_str(), Style::default().fg(Color::Red)),
+        None => Line::from(format!(
+            "{} files, {} matched, {changed} would change | Tab: next field, ↑/↓: scroll, Enter: accept, Esc: quit",
+            state.files.len(),
+            state.matched.len()
+        )),
+    };
+    frame.render_widget(Paragraph::new(status), rows[4]);
+}
+
+fn run(terminal: &mut DefaultTerminal, state: &mut TuiState) -> std::io::Result<bool> {
+    loop {
+        terminal.draw(|frame| draw(frame, state))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Esc => return Ok(false),
+            KeyCode::Enter => return Ok(true),
+            KeyCode::Tab => {
+                state.focus = match state.focus {
+                    Field::Matcher => Field::Renamer,
+                    Field::Renamer => Field::Replacement,
+                    Field::Replacement => Field::Matcher,
+                }
+            }
+            KeyCode::Up => state.scroll = state.scroll.saturating_sub(1),
+            KeyCode::Down => {
+                state.scroll = (state.scroll + 1).min(state.matched.len().saturating_sub(1))
+            }
+            KeyCode::Backspace => {
+                state.input().pop();
+                state.update();
+            }
+            KeyCode::Char(c) => {
+                state.input().push(c);
+                state.update();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// runs the TUI until the user accepts (returning the regexes) or quits (returning None)
+pub fn run_tui(
+    files: &[PathBuf],
+    base_path: &str,
+    config: &Config,
+) -> std::io::Result<Option<TuiResult>> {
+    let mut state = TuiState {
+        files,
+        base_path,
+        config: config.clone(),
+        focus: Field::Matcher,
+        scroll: 0,
+        matched: Vec::new(),
+        preview: Vec::new(),
+        error: None,
+    };
+    state.update();
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut state);
+    ratatui::restore();
+
+    Ok(match result? {
+        true => Some(TuiResult {
+            matcher: state.config.matcher_string,
+            renamer: state.config.renamer_string,
+            replacement: state.config.replacement_string,
+        }),
+        false => None,
+    })
+}
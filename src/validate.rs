@@ -0,0 +1,117 @@
+//! Checks for config mistakes which are valid, but probably not what was meant.
+
+use std::fmt::Display;
+
+use regex::Regex;
+
+use renamer::Config;
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigWarning {
+    /// the renamer only matches at the end, but the matcher only finds names with a different ending
+    InconsistentRegexes { renamer: String, ending: String },
+    /// `$0` in the replacement is the whole match, not the capture group
+    ReplacesWholeMatch,
+    /// unchanged files are hidden before anything's been shown
+    ShowUnchangedOff,
+    /// the renamer has nothing to put in the replacement
+    NoCaptureGroups,
+}
+
+impl Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigWarning::InconsistentRegexes { renamer, ending } => write!(
+                f,
+                "The renamer regex {renamer} only matches at the end of names, but everything the file matcher finds ends in '{ending}', which it doesn't match."
+            ),
+            ConfigWarning::ReplacesWholeMatch => write!(
+                f,
+                "The replacement contains $0, which is the whole renamer match rather than the capture group, use $1 for that."
+            ),
+            ConfigWarning::ShowUnchangedOff => write!(
+                f,
+                "Unchanged files are hidden, so the preview won't show which files the renamer misses."
+            ),
+            ConfigWarning::NoCaptureGroups => write!(
+                f,
+                "The renamer regex doesn't have a capture group, wrap the part to rename in brackets."
+            ),
+        }
+    }
+}
+
+/// the literal characters at the end of a pattern, including escaped punctuation, eg `.jpeg` from `.*\.jpeg$`
+fn literal_ending(pattern: &str) -> String {
+    let mut chars: Vec<char> = pattern
+        .strip_suffix('$')
+        .unwrap_or(pattern)
+        .chars()
+        .collect();
+    let mut ending = Vec::new();
+    while let Some(c) = chars.pop() {
+        match chars.last() {
+            Some('\\') if c.is_ascii_punctuation() => {
+                chars.pop();
+                ending.push(c);
+            }
+            Some('\\') => break,
+            _ if c.is_alphanumeric() || c == '_' || c == '-' => ending.push(c),
+            _ => break,
+        }
+    }
+    ending.into_iter().rev().collect()
+}
+
+pub fn validate_config(config: &Config) -> Vec<ConfigWarning> {
+    let mut warnings = Vec::new();
+
+    if config.uses_renamer_regex() {
+        if let Ok(renamer) = Regex::new(&config.renamer_pattern()) {
+            let ending = literal_ending(&config.matcher_string);
+            if config.renamer_string.ends_with('$')
+                && !config.renamer_string.ends_with("\\$")
+                && !ending.is_empty()
+                && !config.invert_match
+                && !renamer.is_match(&ending)
+            {
+                warnings.push(ConfigWarning::InconsistentRegexes {
+                    renamer: config.renamer_string.clone(),
+                    ending,
+                });
+            }
+            if renamer.captures_len() == 1 && !config.skip_regex_validation {
+                warnings.push(ConfigWarning::NoCaptureGroups);
+            }
+        }
+        if !config.rename_template
+            && (config.replacement_string.contains("$0")
+                || config.replacement_string.contains("${0}"))
+        {
+            warnings.push(ConfigWarning::ReplacesWholeMatch);
+        }
+    }
+
+    if !config.show_unchanged {
+        warnings.push(ConfigWarning::ShowUnchangedOff);
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_ending_includes_escaped_punctuation() {
+        assert_eq!(literal_ending(r".*\.jpeg$"), ".jpeg");
+        assert_eq!(literal_ending(r".*_final-v2"), "_final-v2");
+    }
+
+    #[test]
+    fn literal_ending_stops_at_regex_syntax() {
+        assert_eq!(literal_ending(r".*\.jpe?g$"), "g");
+        assert_eq!(literal_ending(r"photo\d$"), "");
+        assert_eq!(literal_ending(r".*\.(jpeg|png)$"), "");
+    }
+}
@@ -0,0 +1,61 @@
+//! Building a [Config] from outside the crate, the way another program using renamer would.
+
+use std::fs::File;
+
+use regex::Regex;
+use renamer::config::{ConflictStrategy, MatchType};
+use renamer::{get_change_pairs, get_matched_paths, Config};
+
+#[test]
+fn every_setter_is_usable_from_outside_the_crate() {
+    let config = Config::builder()
+        .matcher(r".*\.jpeg")
+        .renamer("(jpeg)$")
+        .replacement("jpg")
+        .show_unchanged(false)
+        .match_type(MatchType::File)
+        .rename_conflicts(ConflictStrategy::Suffix)
+        .build()
+        .expect("Failed to build config");
+    assert!(!config.show_unchanged);
+    assert_eq!(config.match_type, MatchType::File);
+    assert_eq!(config.rename_conflicts, ConflictStrategy::Suffix);
+}
+
+#[test]
+fn an_invalid_matcher_is_an_error() {
+    assert!(Config::builder().matcher("(").build().is_err());
+}
+
+#[test]
+fn matched_paths_are_paired_with_their_new_names() {
+    let tree = tempfile::tempdir().expect("Failed to create temporary directory");
+    File::create(tree.path().join("photo.jpeg")).expect("Failed to create file");
+    File::create(tree.path().join("notes.txt")).expect("Failed to create file");
+    let base_path = tree.path().to_string_lossy().to_string();
+    let config = Config::builder()
+        .matcher(r".*\.jpeg")
+        .renamer("(jpeg)$")
+        .replacement("jpg")
+        .build()
+        .expect("Failed to build config");
+
+    let paths = get_matched_paths(
+        &base_path,
+        &config,
+        Regex::new(r"^.*\.jpeg$").expect("Failed to build matcher"),
+    );
+    let changeset = get_change_pairs(
+        paths,
+        base_path,
+        Some(Regex::new("(jpeg)$").expect("Failed to build renamer")),
+        &config,
+    );
+    assert_eq!(
+        changeset.changes,
+        vec![(
+            tree.path().join("photo.jpeg"),
+            tree.path().join("photo.jpg")
+        )]
+    );
+}